@@ -5,6 +5,28 @@ use std::path::PathBuf;
 pub enum Format {
     Text,
     Json,
+    Csv,
+    Tsv,
+    Markdown,
+    Html,
+    SvgTreemap,
+    Dot,
+    Flat,
+}
+
+/// Order of sibling directories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Name,
+    Count,
+    Natural,
+}
+
+/// Order of `--flat` lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlatSort {
+    Path,
+    Count,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,6 +35,13 @@ pub enum CountMode {
     Tree,
 }
 
+/// What a directory's count measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountBy {
+    Files,
+    Bytes,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HeaderMode {
     Include,
@@ -25,17 +54,35 @@ pub struct Cli {
     pub path: PathBuf,
     pub langs: Vec<String>,
     pub ext: Vec<String>,
+    pub exclude_ext: Vec<String>,
+    pub languages_files: Vec<PathBuf>,
     pub headers: HeaderMode,
     pub count_mode: CountMode,
+    pub count_by: CountBy,
+    pub lines: bool,
     pub max_depth: Option<usize>,
+    pub prune: bool,
     pub min_count: usize,
     pub show_empty: bool,
+    pub sort: SortBy,
+    pub reverse: bool,
+    pub collapse: bool,
+    pub top: Option<usize>,
     pub follow_symlinks: bool,
+    pub threads: Option<usize>,
+    pub strict: bool,
     pub exclude: Vec<String>,
+    pub no_default_excludes: bool,
+    pub skip: Vec<String>,
+    pub no_skip: Vec<String>,
     pub no_gitignore: bool,
+    pub no_git_excludes: bool,
     pub hidden: bool,
     pub format: Format,
+    pub flat_sort: FlatSort,
+    pub null: bool,
     pub json_pretty: bool,
+    pub list_languages: bool,
 }
 
 impl Cli {
@@ -47,17 +94,36 @@ impl Cli {
         let mut path: Option<PathBuf> = None;
         let mut langs = vec![];
         let mut ext = vec![];
+        let mut exclude_ext = vec![];
+        let mut languages_files = vec![];
         let mut headers = HeaderMode::Include;
         let mut count_mode = CountMode::Tree;
+        let mut count_by = CountBy::Files;
+        let mut lines = false;
         let mut max_depth = None;
+        let mut prune = false;
         let mut min_count = 0usize;
         let mut show_empty = false;
+        let mut sort = SortBy::Name;
+        let mut reverse = false;
+        let mut collapse = false;
+        let mut top = None;
         let mut follow_symlinks = false;
+        let mut threads = None;
+        let mut strict = false;
         let mut exclude = vec![];
+        let mut no_default_excludes = false;
+        let mut skip = vec![];
+        let mut no_skip = vec![];
         let mut no_gitignore = false;
+        let mut no_git_excludes = false;
         let mut hidden = false;
-        let mut format = Format::Text;
+        let mut format = None;
+        let mut flat = false;
+        let mut flat_sort = None;
+        let mut null = false;
         let mut json_pretty = false;
+        let mut list_languages = false;
 
         let mut i = 0;
         while i < args.len() {
@@ -68,7 +134,7 @@ impl Cli {
                 "--lang" => {
                     i += 1;
                     let v = args.get(i).ok_or("--lang requires a value")?;
-                    langs.extend(parse_langs(v));
+                    langs.extend(parse_list(v));
                 }
                 "--ext" => {
                     i += 1;
@@ -79,6 +145,20 @@ impl Cli {
                         }
                     }
                 }
+                "--exclude-ext" => {
+                    i += 1;
+                    let v = args.get(i).ok_or("--exclude-ext requires a value")?;
+                    for item in v.split(',') {
+                        if let Some(n) = crate::lang::normalize_ext(item) {
+                            exclude_ext.push(n);
+                        }
+                    }
+                }
+                "--languages-file" => {
+                    i += 1;
+                    let v = args.get(i).ok_or("--languages-file requires a value")?;
+                    languages_files.push(PathBuf::from(v));
+                }
                 "--headers" => {
                     i += 1;
                     headers = parse_headers(args.get(i).ok_or("--headers requires a value")?)?;
@@ -88,6 +168,11 @@ impl Cli {
                     count_mode =
                         parse_count_mode(args.get(i).ok_or("--count-mode requires a value")?)?;
                 }
+                "--count-by" => {
+                    i += 1;
+                    count_by = parse_count_by(args.get(i).ok_or("--count-by requires a value")?)?;
+                }
+                "--lines" => lines = true,
                 "--max-depth" => {
                     i += 1;
                     max_depth = Some(parse_usize(
@@ -95,6 +180,7 @@ impl Cli {
                         "max-depth",
                     )?);
                 }
+                "--prune" => prune = true,
                 "--min-count" => {
                     i += 1;
                     min_count = parse_usize(
@@ -103,18 +189,63 @@ impl Cli {
                     )?;
                 }
                 "--show-empty" => show_empty = true,
+                "--sort" => {
+                    i += 1;
+                    sort = parse_sort(args.get(i).ok_or("--sort requires a value")?)?;
+                }
+                "--reverse" => reverse = true,
+                "--collapse" => collapse = true,
+                "--top" => {
+                    i += 1;
+                    let n = parse_usize(args.get(i).ok_or("--top requires a value")?, "top")?;
+                    if n == 0 {
+                        return Err("invalid top: 0".to_string());
+                    }
+                    top = Some(n);
+                }
                 "--follow-symlinks" => follow_symlinks = true,
+                "--threads" => {
+                    i += 1;
+                    let n =
+                        parse_usize(args.get(i).ok_or("--threads requires a value")?, "threads")?;
+                    if n == 0 {
+                        return Err("invalid threads: 0".to_string());
+                    }
+                    threads = Some(n);
+                }
+                "--strict" => strict = true,
                 "--exclude" => {
                     i += 1;
                     exclude.push(args.get(i).ok_or("--exclude requires a value")?.to_string());
                 }
+                "--no-default-excludes" => no_default_excludes = true,
+                "--skip" => {
+                    i += 1;
+                    skip.extend(parse_list(args.get(i).ok_or("--skip requires a value")?));
+                }
+                "--no-skip" => {
+                    i += 1;
+                    no_skip.extend(parse_list(args.get(i).ok_or("--no-skip requires a value")?));
+                }
                 "--no-gitignore" => no_gitignore = true,
+                "--no-git-excludes" => no_git_excludes = true,
                 "--hidden" => hidden = true,
                 "--format" => {
                     i += 1;
-                    format = parse_format(args.get(i).ok_or("--format requires a value")?)?;
+                    format = Some(parse_format(
+                        args.get(i).ok_or("--format requires a value")?,
+                    )?);
+                }
+                "--flat" => flat = true,
+                "--flat-sort" => {
+                    i += 1;
+                    flat_sort = Some(parse_flat_sort(
+                        args.get(i).ok_or("--flat-sort requires a value")?,
+                    )?);
                 }
+                "--null" => null = true,
                 "--json-pretty" => json_pretty = true,
+                "--list-languages" => list_languages = true,
                 s if s.starts_with('-') => return Err(format!("unknown option: {s}")),
                 other => {
                     if path.is_some() {
@@ -126,21 +257,65 @@ impl Cli {
             i += 1;
         }
 
+        let format = match format {
+            Some(f) if flat && f != Format::Flat => {
+                return Err("--flat cannot be combined with another --format".to_string());
+            }
+            Some(f) => f,
+            None if flat => Format::Flat,
+            None => Format::Text,
+        };
+        if prune && max_depth.is_none() {
+            return Err("--prune requires --max-depth".to_string());
+        }
+        if top.is_some() && !matches!(format, Format::Text | Format::Json) {
+            return Err("--top only applies to text and JSON output".to_string());
+        }
+        if list_languages && !matches!(format, Format::Text | Format::Json) {
+            return Err("--list-languages only supports text and JSON output".to_string());
+        }
+        if format != Format::Flat {
+            if null {
+                return Err("--null requires --flat".to_string());
+            }
+            if flat_sort.is_some() {
+                return Err("--flat-sort requires --flat".to_string());
+            }
+        }
+
         Ok(Self {
             path: path.unwrap_or_else(|| PathBuf::from(".")),
             langs,
             ext,
+            exclude_ext,
+            languages_files,
             headers,
             count_mode,
+            count_by,
+            lines,
             max_depth,
+            prune,
             min_count,
             show_empty,
+            sort,
+            reverse,
+            collapse,
+            top,
             follow_symlinks,
+            threads,
+            strict,
             exclude,
+            no_default_excludes,
+            skip,
+            no_skip,
             no_gitignore,
+            no_git_excludes,
             hidden,
             format,
+            flat_sort: flat_sort.unwrap_or(FlatSort::Path),
+            null,
             json_pretty,
+            list_languages,
         })
     }
 }
@@ -150,7 +325,7 @@ fn parse_usize(v: &str, field: &str) -> Result<usize, String> {
         .map_err(|_| format!("invalid {field}: {v}"))
 }
 
-fn parse_langs(v: &str) -> Vec<String> {
+fn parse_list(v: &str) -> Vec<String> {
     v.split(',')
         .map(str::trim)
         .filter(|s| !s.is_empty())
@@ -175,16 +350,48 @@ fn parse_count_mode(v: &str) -> Result<CountMode, String> {
     }
 }
 
+fn parse_count_by(v: &str) -> Result<CountBy, String> {
+    match v.to_ascii_lowercase().as_str() {
+        "files" => Ok(CountBy::Files),
+        "bytes" => Ok(CountBy::Bytes),
+        _ => Err(format!("invalid --count-by value: {v}")),
+    }
+}
+
+fn parse_sort(v: &str) -> Result<SortBy, String> {
+    match v.to_ascii_lowercase().as_str() {
+        "name" => Ok(SortBy::Name),
+        "count" => Ok(SortBy::Count),
+        "natural" => Ok(SortBy::Natural),
+        _ => Err(format!("invalid --sort value: {v}")),
+    }
+}
+
+fn parse_flat_sort(v: &str) -> Result<FlatSort, String> {
+    match v.to_ascii_lowercase().as_str() {
+        "path" => Ok(FlatSort::Path),
+        "count" => Ok(FlatSort::Count),
+        _ => Err(format!("invalid --flat-sort value: {v}")),
+    }
+}
+
 fn parse_format(v: &str) -> Result<Format, String> {
     match v.to_ascii_lowercase().as_str() {
         "text" => Ok(Format::Text),
         "json" => Ok(Format::Json),
+        "csv" => Ok(Format::Csv),
+        "tsv" => Ok(Format::Tsv),
+        "markdown" | "md" => Ok(Format::Markdown),
+        "html" => Ok(Format::Html),
+        "svg-treemap" => Ok(Format::SvgTreemap),
+        "dot" => Ok(Format::Dot),
+        "flat" => Ok(Format::Flat),
         _ => Err(format!("invalid --format value: {v}")),
     }
 }
 
 pub fn print_help() {
     println!(
-        "treestat [PATH] [OPTIONS]\n\nOptions:\n  --lang <LANG[,LANG...]> (repeatable, aliases from Linguist)\n  --ext <a,b,c>\n  --headers <include|exclude|only>\n  --count-mode <direct|tree>\n  --max-depth <N>\n  --min-count <N>\n  --show-empty\n  --follow-symlinks\n  --exclude <PATTERN> (repeatable)\n  --no-gitignore\n  --hidden\n  --format <text|json>\n  --json-pretty\n  -h, --help\n  -V, --version"
+        "treestat [PATH] [OPTIONS]\n\nOptions:\n  --lang <LANG[,LANG...]> (repeatable, aliases from Linguist)\n  --ext <a,b,c>\n  --exclude-ext <a,b,c>\n  --languages-file <PATH> (repeatable)\n  --headers <include|exclude|only>\n  --count-mode <direct|tree>\n  --count-by <files|bytes>\n  --lines\n  --max-depth <N>\n  --prune\n  --min-count <N>\n  --show-empty\n  --sort <name|count|natural>\n  --reverse\n  --collapse\n  --top <N>\n  --follow-symlinks\n  --threads <N>\n  --strict\n  --exclude <PATTERN> (repeatable)\n  --no-default-excludes\n  --skip <NAME[,NAME...]> (repeatable)\n  --no-skip <NAME[,NAME...]> (repeatable)\n  --no-gitignore\n  --no-git-excludes\n  --hidden\n  --format <text|json|csv|tsv|markdown|html|svg-treemap|dot|flat>\n  --flat\n  --flat-sort <path|count>\n  --null\n  --json-pretty\n  --list-languages\n  -h, --help\n  -V, --version"
     );
 }
@@ -1,35 +1,158 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
-use std::sync::OnceLock;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use crate::cli::HeaderMode;
 
 #[derive(Debug, Default)]
 struct LinguistLanguage {
+    kind: Option<String>,
+    group: Option<String>,
     aliases: Vec<String>,
     extensions: Vec<String>,
+    filenames: Vec<String>,
+    interpreters: Vec<String>,
+    line_comment: Vec<String>,
+    block_comment: Vec<String>,
+    nested_comments: Option<bool>,
+    strings: Vec<String>,
+    multiline_strings: Vec<String>,
+    docstrings: Option<bool>,
+    char_literals: Option<bool>,
+    raw_strings: Option<bool>,
 }
 
+impl LinguistLanguage {
+    fn merge(&mut self, other: LinguistLanguage) {
+        self.kind = other.kind.or(self.kind.take());
+        self.group = other.group.or(self.group.take());
+        self.aliases.extend(other.aliases);
+        self.extensions.extend(other.extensions);
+        self.filenames.extend(other.filenames);
+        self.interpreters.extend(other.interpreters);
+        self.line_comment.extend(other.line_comment);
+        self.block_comment.extend(other.block_comment);
+        self.nested_comments = other.nested_comments.or(self.nested_comments);
+        self.strings.extend(other.strings);
+        self.multiline_strings.extend(other.multiline_strings);
+        self.docstrings = other.docstrings.or(self.docstrings);
+        self.char_literals = other.char_literals.or(self.char_literals);
+        self.raw_strings = other.raw_strings.or(self.raw_strings);
+    }
+}
+
+/// How `--lines` tells comments and strings from code in a language. Delimiters are
+/// `(open, close)` pairs.
+#[derive(Debug, Default, Clone)]
+pub struct CommentSyntax {
+    pub line: Vec<String>,
+    pub block: Vec<(String, String)>,
+    pub nested: bool,
+    /// Strings that end at the end of the line at the latest.
+    pub strings: Vec<(String, String)>,
+    pub multiline_strings: Vec<(String, String)>,
+    /// A multiline string that starts a statement is a docstring, as in Python.
+    pub docstrings: bool,
+    /// `'x'` is a character literal; a `'` that does not start one is code, as in Rust.
+    pub char_literals: bool,
+    /// Rust raw strings: `r"..."`, `r#"..."#` and their `b`/`c` prefixed forms.
+    pub raw_strings: bool,
+}
+
+#[derive(Debug)]
+struct Language {
+    name: String,
+    display_name: String,
+    /// The first extension Linguist lists, which decides ties between languages.
+    primary_extension: Option<String>,
+    kind: Option<String>,
+    group: Option<String>,
+    extensions: HashSet<String>,
+    filenames: HashSet<String>,
+    interpreters: HashSet<String>,
+    comments: CommentSyntax,
+}
+
+/// Every language a run knows about: the embedded Linguist data, plus whatever
+/// `--languages-file` adds.
 #[derive(Debug)]
-struct LanguageRegistry {
+pub struct LanguageRegistry {
     names: HashMap<String, String>,
-    extensions: HashMap<String, HashSet<String>>,
+    languages: BTreeMap<String, Language>,
 }
 
-static REGISTRY: OnceLock<LanguageRegistry> = OnceLock::new();
+/// Aliases from the smaller language list treestat used to bundle, kept so existing
+/// `--lang` values keep working.
+const EXTRA_ALIASES: &[(&str, &str)] = &[("py", "python")];
 
-fn parse_embedded_linguist_yaml() -> HashMap<String, LinguistLanguage> {
-    let mut result = HashMap::new();
+/// Everything a file can be matched on: its extension, its exact name, or the interpreter
+/// named in its shebang line.
+#[derive(Debug, Default, Clone)]
+pub struct Selection {
+    pub extensions: HashSet<String>,
+    pub excluded_extensions: HashSet<String>,
+    pub filenames: HashSet<String>,
+    pub interpreters: HashSet<String>,
+    /// The canonical language each selected extension (and its shorter suffixes), filename
+    /// and interpreter counts as.
+    pub extension_languages: HashMap<String, String>,
+    pub filename_languages: HashMap<String, String>,
+    pub interpreter_languages: HashMap<String, String>,
+}
+
+/// Language name for files matched by an extension no known language claims.
+pub const OTHER_LANGUAGE: &str = "other";
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.extensions.is_empty() && self.filenames.is_empty() && self.interpreters.is_empty()
+    }
+
+    /// Finds the longest dot-separated suffix of `file_name` that is selected or excluded,
+    /// so `a.d.ts` matches `d.ts` before `ts`. Returns the suffix and whether it is selected.
+    pub fn match_extension(&self, file_name: &str) -> Option<(String, bool)> {
+        let lower = file_name.to_ascii_lowercase();
+        let name = lower.strip_prefix('.').unwrap_or(&lower);
+        name.match_indices('.').find_map(|(idx, _)| {
+            let suffix = &name[idx + 1..];
+            if self.excluded_extensions.contains(suffix) {
+                Some((suffix.to_string(), false))
+            } else if self.extensions.contains(suffix) {
+                Some((suffix.to_string(), true))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The language a matched extension counts as. A compound suffix nobody claims, such
+    /// as `d.ts`, falls back to its shorter suffixes.
+    pub fn extension_language(&self, suffix: &str) -> &str {
+        std::iter::once(suffix)
+            .chain(suffix.match_indices('.').map(|(idx, _)| &suffix[idx + 1..]))
+            .find_map(|s| self.extension_languages.get(s))
+            .map_or(OTHER_LANGUAGE, String::as_str)
+    }
+}
+
+/// Parses the subset of YAML used by Linguist's `languages.yml`: top-level language keys
+/// (optionally quoted), scalar fields such as `type` and `group`, and block or flow lists.
+/// Fields treestat does not use are skipped.
+fn parse_linguist_yaml(content: &str) -> BTreeMap<String, LinguistLanguage> {
+    let mut result = BTreeMap::new();
     let mut current_lang: Option<String> = None;
-    let mut current_field: Option<&str> = None;
+    let mut current_field: Option<String> = None;
 
-    for line in include_str!("../data/linguist_languages.yml").lines() {
+    for line in content.lines() {
         let raw = line.trim();
-        if raw.is_empty() || raw.starts_with('#') {
+        if raw.is_empty() || raw.starts_with('#') || raw == "---" {
             continue;
         }
 
-        if !line.starts_with(' ') && raw.ends_with(':') {
-            let lang = raw.trim_end_matches(':').to_string();
+        if !line.starts_with([' ', '\t']) {
+            let Some(key) = raw.strip_suffix(':') else {
+                current_lang = None;
+                continue;
+            };
+            let lang = unquote(key.trim());
             result
                 .entry(lang.clone())
                 .or_insert_with(LinguistLanguage::default);
@@ -38,26 +161,37 @@ fn parse_embedded_linguist_yaml() -> HashMap<String, LinguistLanguage> {
             continue;
         }
 
-        if raw.starts_with("aliases:") {
-            current_field = Some("aliases");
+        let Some(entry) = current_lang.as_ref().and_then(|l| result.get_mut(l)) else {
             continue;
-        }
-        if raw.starts_with("extensions:") {
-            current_field = Some("extensions");
+        };
+
+        if let Some(item) = raw.strip_prefix('-') {
+            if let Some(field) = &current_field {
+                push_field(entry, field, unquote(item.trim()));
+            }
             continue;
         }
 
-        if raw.starts_with("- ") {
-            let Some(lang) = current_lang.as_ref() else {
-                continue;
-            };
-            let value = raw.trim_start_matches("- ").trim().to_string();
-            let entry = result
-                .entry(lang.clone())
-                .or_insert_with(LinguistLanguage::default);
-            match current_field {
-                Some("aliases") => entry.aliases.push(value),
-                Some("extensions") => entry.extensions.push(value),
+        let Some((key, value)) = raw.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim();
+        current_field = None;
+        if value.is_empty() {
+            current_field = Some(key.to_string());
+        } else if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            for item in items.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+                push_field(entry, key, unquote(item));
+            }
+        } else {
+            match key {
+                "type" => entry.kind = Some(unquote(value)),
+                "group" => entry.group = Some(unquote(value)),
+                "nested_comments" => entry.nested_comments = Some(value == "true"),
+                "docstrings" => entry.docstrings = Some(value == "true"),
+                "char_literals" => entry.char_literals = Some(value == "true"),
+                "raw_strings" => entry.raw_strings = Some(value == "true"),
                 _ => {}
             }
         }
@@ -66,42 +200,378 @@ fn parse_embedded_linguist_yaml() -> HashMap<String, LinguistLanguage> {
     result
 }
 
-fn registry() -> &'static LanguageRegistry {
-    REGISTRY.get_or_init(|| {
-        let raw = parse_embedded_linguist_yaml();
+fn push_field(entry: &mut LinguistLanguage, field: &str, value: String) {
+    match field {
+        "aliases" => entry.aliases.push(value),
+        "extensions" => entry.extensions.push(value),
+        "filenames" => entry.filenames.push(value),
+        "interpreters" => entry.interpreters.push(value),
+        "line_comment" => entry.line_comment.push(value),
+        "block_comment" => entry.block_comment.push(value),
+        "strings" => entry.strings.push(value),
+        "multiline_strings" => entry.multiline_strings.push(value),
+        _ => {}
+    }
+}
+
+/// Splits `"/* */"` into an open/close pair; a single delimiter such as `"` closes itself.
+fn delimiters(raw: &str) -> Option<(String, String)> {
+    let mut parts = raw.split_whitespace();
+    let open = parts.next()?;
+    let close = parts.next().unwrap_or(open);
+    parts
+        .next()
+        .is_none()
+        .then(|| (open.to_string(), close.to_string()))
+}
+
+fn unquote(v: &str) -> String {
+    if v.len() >= 2 && v.starts_with('"') && v.ends_with('"') {
+        return v[1..v.len() - 1]
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\");
+    }
+    if v.len() >= 2 && v.starts_with('\'') && v.ends_with('\'') {
+        return v[1..v.len() - 1].replace("''", "'");
+    }
+    v.to_string()
+}
+
+/// A registry entry as shown by `--list-languages`.
+#[derive(Debug, Clone)]
+pub struct LanguageInfo {
+    pub name: String,
+    pub display_name: String,
+    pub kind: Option<String>,
+    pub group: Option<String>,
+    pub aliases: Vec<String>,
+    pub extensions: Vec<String>,
+    pub filenames: Vec<String>,
+    pub interpreters: Vec<String>,
+}
+
+impl LanguageRegistry {
+    /// Extends the built-in language data with user-defined languages, given as the
+    /// contents of files in the same shape as `linguist_languages.yml`. An entry named like
+    /// a built-in language adds to it; a new entry without a `type` counts as `programming`.
+    pub fn new(user_files: &[(String, String)]) -> Result<Self, String> {
+        let mut user = vec![];
+        for (source, content) in user_files {
+            let langs = parse_linguist_yaml(content);
+            if langs.is_empty() {
+                return Err(format!("no languages defined in {source}"));
+            }
+            user.push(langs);
+        }
+        Ok(build_registry(user))
+    }
+
+    /// Resolves a `--lang` value to the languages it covers: `all` means every programming
+    /// language, and a language also covers the languages grouped under it (TSX under
+    /// TypeScript, for example).
+    fn resolve_languages(&self, raw: &str) -> Result<Vec<&Language>, String> {
+        if raw.eq_ignore_ascii_case("all") {
+            return Ok(self
+                .languages
+                .values()
+                .filter(|l| l.kind.as_deref() == Some("programming"))
+                .collect());
+        }
+
+        let canonical = self
+            .canonical_language_name(raw)
+            .ok_or_else(|| format!("invalid --lang value: {raw}"))?;
+        Ok(self
+            .languages
+            .iter()
+            .filter(|(name, l)| **name == canonical || l.group.as_ref() == Some(&canonical))
+            .map(|(_, l)| l)
+            .collect())
+    }
+
+    /// Canonical (lowercase) names of every known language, sorted.
+    pub fn language_names(&self) -> Vec<String> {
+        self.languages.keys().cloned().collect()
+    }
+
+    /// Every language in the registry, sorted by canonical name.
+    pub fn languages(&self) -> Vec<LanguageInfo> {
+        let mut aliases: HashMap<&str, Vec<String>> = HashMap::new();
+        for (alias, canonical) in &self.names {
+            if alias != canonical {
+                aliases.entry(canonical).or_default().push(alias.clone());
+            }
+        }
+
+        let sorted = |set: &HashSet<String>| {
+            let mut v = set.iter().cloned().collect::<Vec<_>>();
+            v.sort();
+            v
+        };
+        self.languages
+            .iter()
+            .map(|(name, lang)| {
+                let mut alias_list = aliases.remove(name.as_str()).unwrap_or_default();
+                alias_list.sort();
+                LanguageInfo {
+                    name: name.clone(),
+                    display_name: lang.display_name.clone(),
+                    kind: lang.kind.clone(),
+                    group: lang.group.clone(),
+                    aliases: alias_list,
+                    extensions: sorted(&lang.extensions),
+                    filenames: sorted(&lang.filenames),
+                    interpreters: sorted(&lang.interpreters),
+                }
+            })
+            .collect()
+    }
+
+    /// Comment syntax of a canonical language; empty for languages without one.
+    pub fn comment_syntax(&self, language: &str) -> Option<&CommentSyntax> {
+        self.languages.get(language).map(|l| &l.comments)
+    }
+
+    pub fn canonical_language_name(&self, raw: &str) -> Option<String> {
+        self.names.get(&raw.to_ascii_lowercase()).cloned()
+    }
+
+    pub fn build_extensions(
+        &self,
+        langs: &[String],
+        ext: &[String],
+        headers: HeaderMode,
+    ) -> Result<HashSet<String>, String> {
+        let mut selected = HashSet::new();
 
-        let mut names = HashMap::new();
-        let mut extensions = HashMap::new();
+        for e in ext {
+            if let Some(n) = normalize_ext(e) {
+                selected.insert(n);
+            }
+        }
 
-        for (lang, spec) in raw {
-            let canonical = lang.to_ascii_lowercase();
-            names.insert(canonical.clone(), canonical.clone());
-            for alias in spec.aliases {
-                names.insert(alias.to_ascii_lowercase(), canonical.clone());
+        let requested_langs = if langs.is_empty() {
+            vec!["all".to_string()]
+        } else {
+            langs.to_vec()
+        };
+
+        for raw in &requested_langs {
+            for lang in self.resolve_languages(raw)? {
+                selected.extend(lang.extensions.iter().cloned());
             }
+        }
 
-            let ext_set = spec
-                .extensions
-                .into_iter()
-                .filter_map(|e| normalize_ext(&e))
-                .collect::<HashSet<_>>();
-            extensions.insert(canonical, ext_set);
+        if requested_langs.iter().any(|l| {
+            l.eq_ignore_ascii_case("all")
+                || self
+                    .canonical_language_name(l)
+                    .is_some_and(|name| name == "c" || name == "c++")
+        }) {
+            apply_header_mode(&mut selected, headers);
         }
 
-        LanguageRegistry { names, extensions }
-    })
+        Ok(selected)
+    }
+
+    /// Resolves `--lang`, `--ext` and `--headers` into everything the scanner should match.
+    pub fn build_selection(
+        &self,
+        langs: &[String],
+        ext: &[String],
+        exclude_ext: &[String],
+        headers: HeaderMode,
+    ) -> Result<Selection, String> {
+        let extensions = self.build_extensions(langs, ext, headers)?;
+        let mut selection = Selection {
+            excluded_extensions: exclude_ext
+                .iter()
+                .filter_map(|e| normalize_ext(e))
+                .collect(),
+            extensions,
+            ..Selection::default()
+        };
+
+        let requested = if langs.is_empty() {
+            vec!["all".to_string()]
+        } else {
+            langs.to_vec()
+        };
+        let mut resolved = BTreeMap::new();
+        for raw in &requested {
+            for lang in self.resolve_languages(raw)? {
+                resolved.insert(lang.name.as_str(), lang);
+            }
+        }
+
+        // Known filenames and shebang scripts are never headers.
+        let by_name = if headers == HeaderMode::Only {
+            vec![]
+        } else {
+            resolved.values().collect()
+        };
+        for lang in by_name {
+            selection.filenames.extend(lang.filenames.iter().cloned());
+            selection
+                .interpreters
+                .extend(lang.interpreters.iter().cloned());
+            for filename in &lang.filenames {
+                selection
+                    .filename_languages
+                    .entry(filename.clone())
+                    .or_insert_with(|| lang.name.clone());
+            }
+            for interpreter in &lang.interpreters {
+                selection
+                    .interpreter_languages
+                    .entry(interpreter.clone())
+                    .or_insert_with(|| lang.name.clone());
+            }
+        }
+
+        // Requested languages claim their extensions first; extensions added by `--ext` or
+        // `--headers` fall back to any language that knows them, or knows a shorter suffix
+        // (`d.ts` counts as TypeScript).
+        let suffixes = selection
+            .extensions
+            .iter()
+            .flat_map(|e| {
+                std::iter::once(e.as_str())
+                    .chain(e.match_indices('.').map(|(idx, _)| &e[idx + 1..]))
+            })
+            .map(str::to_string)
+            .collect::<HashSet<_>>();
+        let all = self.languages.values().collect::<Vec<_>>();
+        let requested = resolved.into_values().collect::<Vec<_>>();
+        for candidates in [&requested, &all] {
+            assign_extensions(&mut selection.extension_languages, &suffixes, candidates);
+        }
+
+        Ok(selection)
+    }
+
+    /// The canonical names of the given `--lang` values, sorted and without duplicates.
+    /// Values that name no language are kept lowercased.
+    pub fn canonical_names(&self, langs: &[String]) -> Vec<String> {
+        langs
+            .iter()
+            .map(|lang| {
+                self.canonical_language_name(lang)
+                    .unwrap_or_else(|| lang.to_ascii_lowercase())
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
 }
 
-pub fn canonical_language_name(raw: &str) -> Option<String> {
-    registry().names.get(&raw.to_ascii_lowercase()).cloned()
+/// Just the embedded language data.
+impl Default for LanguageRegistry {
+    fn default() -> Self {
+        build_registry(vec![])
+    }
 }
 
+fn build_registry(user: Vec<BTreeMap<String, LinguistLanguage>>) -> LanguageRegistry {
+    let mut raw = parse_linguist_yaml(include_str!("../data/linguist_languages.yml"));
+    for (lang, syntax) in parse_linguist_yaml(include_str!("../data/comment_syntax.yml")) {
+        if let Some(entry) = raw.get_mut(&lang) {
+            entry.merge(syntax);
+        }
+    }
+
+    let mut user_aliases = vec![];
+    for (lang, spec) in user.into_iter().flatten() {
+        let key = raw
+            .keys()
+            .find(|k| k.eq_ignore_ascii_case(&lang))
+            .cloned()
+            .unwrap_or(lang);
+        for alias in &spec.aliases {
+            user_aliases.push((alias.to_ascii_lowercase(), key.to_ascii_lowercase()));
+        }
+        match raw.get_mut(&key) {
+            Some(entry) => entry.merge(spec),
+            None => {
+                let mut spec = spec;
+                spec.kind.get_or_insert_with(|| "programming".to_string());
+                raw.insert(key, spec);
+            }
+        }
+    }
+
+    let mut names = HashMap::new();
+    let mut languages = BTreeMap::new();
+
+    // Canonical names win over aliases of other languages, and user aliases over built-in ones.
+    for lang in raw.keys() {
+        let canonical = lang.to_ascii_lowercase();
+        names.insert(canonical.clone(), canonical);
+    }
+    for (alias, canonical) in user_aliases {
+        names.entry(alias).or_insert(canonical);
+    }
+    for (lang, spec) in raw {
+        let canonical = lang.to_ascii_lowercase();
+        for alias in &spec.aliases {
+            names
+                .entry(alias.to_ascii_lowercase())
+                .or_insert_with(|| canonical.clone());
+        }
+
+        languages.insert(
+            canonical.clone(),
+            Language {
+                name: canonical,
+                display_name: lang,
+                primary_extension: spec.extensions.first().and_then(|e| normalize_ext(e)),
+                kind: spec.kind,
+                group: spec.group.map(|g| g.to_ascii_lowercase()),
+                extensions: spec
+                    .extensions
+                    .iter()
+                    .filter_map(|e| normalize_ext(e))
+                    .collect(),
+                filenames: spec.filenames.into_iter().collect(),
+                interpreters: spec.interpreters.into_iter().collect(),
+                comments: CommentSyntax {
+                    line: spec.line_comment,
+                    block: spec
+                        .block_comment
+                        .iter()
+                        .filter_map(|d| delimiters(d))
+                        .collect(),
+                    nested: spec.nested_comments.unwrap_or(false),
+                    strings: spec.strings.iter().filter_map(|d| delimiters(d)).collect(),
+                    multiline_strings: spec
+                        .multiline_strings
+                        .iter()
+                        .filter_map(|d| delimiters(d))
+                        .collect(),
+                    docstrings: spec.docstrings.unwrap_or(false),
+                    char_literals: spec.char_literals.unwrap_or(false),
+                    raw_strings: spec.raw_strings.unwrap_or(false),
+                },
+            },
+        );
+    }
+    for (alias, canonical) in EXTRA_ALIASES {
+        names
+            .entry(alias.to_string())
+            .or_insert_with(|| canonical.to_string());
+    }
+
+    LanguageRegistry { names, languages }
+}
+
+/// Normalizes an extension such as `.RS` or `d.ts` to lowercase without the leading dot.
+/// Compound extensions are kept whole; ones with empty segments (`d..ts`) are rejected.
 pub fn normalize_ext(raw: &str) -> Option<String> {
-    let trimmed = raw.trim();
-    if trimmed.is_empty() {
+    let trimmed = raw.trim().trim_start_matches('.');
+    if trimmed.is_empty() || trimmed.split('.').any(str::is_empty) {
         return None;
     }
-    Some(trimmed.trim_start_matches('.').to_ascii_lowercase())
+    Some(trimmed.to_ascii_lowercase())
 }
 
 pub fn apply_header_mode(exts: &mut HashSet<String>, headers: HeaderMode) {
@@ -116,62 +586,56 @@ pub fn apply_header_mode(exts: &mut HashSet<String>, headers: HeaderMode) {
     }
 }
 
-pub fn build_extensions(
-    langs: &[String],
-    ext: &[String],
-    headers: HeaderMode,
-) -> Result<HashSet<String>, String> {
-    let mut selected = HashSet::new();
-
-    for e in ext {
-        if let Some(n) = normalize_ext(e) {
-            selected.insert(n);
+/// Gives each unassigned extension to the language that lists it first, or else to the
+/// alphabetically first language that has it, so `.h` counts as C rather than C++.
+fn assign_extensions(
+    owners: &mut HashMap<String, String>,
+    extensions: &HashSet<String>,
+    candidates: &[&Language],
+) {
+    for lang in candidates {
+        if let Some(ext) = &lang.primary_extension
+            && extensions.contains(ext)
+        {
+            owners
+                .entry(ext.clone())
+                .or_insert_with(|| lang.name.clone());
         }
     }
-
-    let requested_langs = if langs.is_empty() {
-        vec!["all".to_string()]
-    } else {
-        langs.to_vec()
-    };
-
-    for raw in &requested_langs {
-        if raw.eq_ignore_ascii_case("all") {
-            for exts in registry().extensions.values() {
-                selected.extend(exts.iter().cloned());
-            }
-            continue;
+    for lang in candidates {
+        for ext in lang.extensions.intersection(extensions) {
+            owners
+                .entry(ext.clone())
+                .or_insert_with(|| lang.name.clone());
         }
-
-        let canonical =
-            canonical_language_name(raw).ok_or_else(|| format!("invalid --lang value: {raw}"))?;
-        let exts = registry()
-            .extensions
-            .get(&canonical)
-            .ok_or_else(|| format!("language has no extensions: {raw}"))?;
-        selected.extend(exts.iter().cloned());
     }
+}
 
-    if requested_langs.iter().any(|l| {
-        l.eq_ignore_ascii_case("all")
-            || canonical_language_name(l).is_some_and(|name| name == "c" || name == "c++")
-    }) {
-        apply_header_mode(&mut selected, headers);
+/// Extracts the interpreter name from a `#!` line, looking through `env` and dropping
+/// trailing version suffixes such as `python3.12` -> `python3`.
+pub fn interpreter_from_shebang(line: &str) -> Option<String> {
+    let rest = line.strip_prefix("#!")?.trim();
+    let mut args = rest.split_whitespace();
+    let mut script = args.next()?.rsplit('/').next()?;
+    if script == "env" {
+        script = args.find(|a| !a.starts_with('-') && !a.contains('='))?;
     }
 
-    Ok(selected)
+    let mut script = script.to_string();
+    while let Some((head, tail)) = script.rsplit_once('.') {
+        if tail.is_empty() || !tail.bytes().all(|b| b.is_ascii_digit()) {
+            break;
+        }
+        script.truncate(head.len());
+    }
+    (!script.is_empty()).then_some(script)
 }
 
+/// Joins canonical language names (see [`LanguageRegistry::canonical_names`]) for titles;
+/// no names means every language.
 pub fn display_langs(langs: &[String]) -> String {
     if langs.is_empty() {
         return "all".to_string();
     }
-
-    let mut normalized = BTreeSet::new();
-    for lang in langs {
-        let canonical = canonical_language_name(lang).unwrap_or_else(|| lang.to_ascii_lowercase());
-        normalized.insert(canonical);
-    }
-
-    normalized.into_iter().collect::<Vec<_>>().join(",")
+    langs.join(",")
 }
@@ -1,5 +1,7 @@
 pub mod cli;
+pub mod json;
 pub mod lang;
+pub mod lines;
 pub mod model;
 pub mod render;
 pub mod scanner;
@@ -7,12 +9,35 @@ pub mod scanner;
 use std::path::Path;
 
 use cli::{Cli, Format};
-use lang::build_extensions;
-use render::{render_json, render_text};
+use lang::LanguageRegistry;
+use render::{
+    render_dot, render_flat, render_html, render_json, render_language_list, render_markdown,
+    render_svg_treemap, render_table, render_text,
+};
 use scanner::{compute_tree_counts, scan_tree};
 
 pub fn run(cli: Cli) -> Result<String, String> {
     let start = std::time::Instant::now();
+    let mut files = vec![];
+    for path in &cli.languages_files {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read languages file {}: {e}", path.display()))?;
+        files.push((path.display().to_string(), content));
+    }
+    let registry = LanguageRegistry::new(&files)?;
+    let langs = registry.canonical_names(&cli.langs);
+    if cli.list_languages {
+        let selection =
+            registry.build_selection(&cli.langs, &cli.ext, &cli.exclude_ext, cli.headers)?;
+        return Ok(render_language_list(
+            &registry.languages(),
+            &selection,
+            &langs,
+            cli.format,
+            cli.json_pretty,
+        ));
+    }
+
     let root = cli
         .path
         .canonicalize()
@@ -21,34 +46,52 @@ pub fn run(cli: Cli) -> Result<String, String> {
         return Err(format!("path is not a directory: {}", root.display()));
     }
 
-    let extensions = build_extensions(&cli.langs, &cli.ext, cli.headers)?;
-    if extensions.is_empty() {
+    let selection =
+        registry.build_selection(&cli.langs, &cli.ext, &cli.exclude_ext, cli.headers)?;
+    if selection.is_empty() {
         return Err("no extensions selected; provide --lang or --ext".to_string());
     }
 
-    let gitignore_patterns = if cli.no_gitignore {
-        vec![]
-    } else {
-        scanner::load_gitignore_patterns(Path::new(&root))
-    };
+    // Ordered from lowest to highest precedence, matching git.
+    let mut gitignore_patterns = vec![];
+    if !cli.no_gitignore {
+        if !cli.no_git_excludes {
+            gitignore_patterns.extend(scanner::load_global_excludes(&root));
+            gitignore_patterns.extend(scanner::load_info_exclude(&root));
+        }
+        gitignore_patterns.extend(scanner::load_gitignore_patterns(&root));
+    }
 
-    let scan = scan_tree(Path::new(&root), &extensions, &cli, &gitignore_patterns)?;
+    let scan = scan_tree(
+        Path::new(&root),
+        &registry,
+        &selection,
+        &cli,
+        &gitignore_patterns,
+    )?;
     let tree_counts = compute_tree_counts(Path::new(&scan.root), &scan.dirs);
     let duration_secs = start.elapsed().as_secs_f64();
 
     let output = match cli.format {
-        Format::Text => {
-            render_text(&scan, &tree_counts, &extensions, &cli.langs, &cli, duration_secs)
-        }
+        Format::Text => render_text(&scan, &tree_counts, &selection, &langs, &cli, duration_secs),
         Format::Json => render_json(
             &scan,
             &tree_counts,
-            &extensions,
-            &cli.langs,
+            &selection,
+            &langs,
             &cli,
             duration_secs,
             cli.json_pretty,
         ),
+        Format::Csv => render_table(&scan, &tree_counts, &cli, ','),
+        Format::Tsv => render_table(&scan, &tree_counts, &cli, '\t'),
+        Format::Markdown => {
+            render_markdown(&scan, &tree_counts, &selection, &langs, &cli, duration_secs)
+        }
+        Format::Html => render_html(&scan, &tree_counts, &selection, &langs, &cli, duration_secs),
+        Format::SvgTreemap => render_svg_treemap(&scan, &tree_counts, &langs, &cli),
+        Format::Dot => render_dot(&scan, &tree_counts, &langs, &cli),
+        Format::Flat => render_flat(&scan, &tree_counts, &cli),
     };
 
     Ok(output)
@@ -0,0 +1,210 @@
+use crate::lang::CommentSyntax;
+use crate::model::LineCounts;
+
+/// What the end of the previous line was still inside of.
+enum State<'a> {
+    Code,
+    Block {
+        close: &'a str,
+        open: &'a str,
+        depth: usize,
+    },
+    Str {
+        close: &'a str,
+        multiline: bool,
+        docstring: bool,
+    },
+    /// A Rust raw string, closed by `"` and this many `#`.
+    Raw {
+        hashes: usize,
+    },
+}
+
+/// Classifies every line of `content` as code, comment or blank. A line with any code on it
+/// is code; comment markers inside strings are ignored, block comments nest when the
+/// language allows it, and docstrings count as comments.
+pub fn count_lines(content: &str, syntax: &CommentSyntax) -> LineCounts {
+    let mut counts = LineCounts::default();
+    let mut state = State::Code;
+
+    for line in content.lines() {
+        if line.trim().is_empty() {
+            counts.blank += 1;
+            continue;
+        }
+
+        let (mut code, mut comment) = match state {
+            State::Code => (false, false),
+            State::Block { .. } => (false, true),
+            State::Str { docstring, .. } => (!docstring, docstring),
+            State::Raw { .. } => (true, false),
+        };
+        let mut rest = line;
+        while !rest.is_empty() {
+            match &mut state {
+                State::Block { close, open, depth } => {
+                    if rest.starts_with(*close) {
+                        rest = &rest[close.len()..];
+                        *depth -= 1;
+                        if *depth == 0 {
+                            state = State::Code;
+                        }
+                    } else if syntax.nested && rest.starts_with(*open) {
+                        rest = &rest[open.len()..];
+                        *depth += 1;
+                    } else {
+                        rest = skip_char(rest);
+                    }
+                }
+                State::Str { close, .. } => {
+                    if rest.starts_with('\\') {
+                        rest = skip_char(skip_char(rest));
+                    } else if rest.starts_with(*close) {
+                        rest = &rest[close.len()..];
+                        state = State::Code;
+                    } else {
+                        rest = skip_char(rest);
+                    }
+                }
+                State::Raw { hashes } => {
+                    if let Some(after) = rest.strip_prefix('"')
+                        && after.bytes().take_while(|&b| b == b'#').count() >= *hashes
+                    {
+                        rest = &after[*hashes..];
+                        state = State::Code;
+                    } else {
+                        rest = skip_char(rest);
+                    }
+                }
+                State::Code => {
+                    let trimmed = rest.trim_start();
+                    if trimmed.len() != rest.len() {
+                        rest = trimmed;
+                        continue;
+                    }
+                    if let Some((open, close)) = longest(&syntax.block, rest) {
+                        comment = true;
+                        rest = &rest[open.len()..];
+                        state = State::Block {
+                            close,
+                            open,
+                            depth: 1,
+                        };
+                        continue;
+                    }
+                    if syntax.line.iter().any(|m| rest.starts_with(m.as_str())) {
+                        comment = true;
+                        break;
+                    }
+
+                    if syntax.char_literals
+                        && let Some(len) = char_literal(rest)
+                    {
+                        code = true;
+                        rest = &rest[len..];
+                        continue;
+                    }
+                    // `r"` only opens a raw string at the start of a token, not in `bar"`.
+                    let prev = line[..line.len() - rest.len()].chars().next_back();
+                    if syntax.raw_strings
+                        && !prev.is_some_and(|c| c.is_alphanumeric() || c == '_')
+                        && let Some((len, hashes)) = raw_string_start(rest)
+                    {
+                        code = true;
+                        rest = &rest[len..];
+                        state = State::Raw { hashes };
+                        continue;
+                    }
+
+                    let multiline = longest(&syntax.multiline_strings, rest);
+                    let single = longest(&syntax.strings, rest);
+                    let string = match (multiline, single) {
+                        (Some(m), Some(s)) if s.0.len() > m.0.len() => Some((s, false)),
+                        (Some(m), _) => Some((m, true)),
+                        (None, Some(s)) => Some((s, false)),
+                        (None, None) => None,
+                    };
+                    if let Some(((open, close), multiline)) = string {
+                        // A docstring is a string statement: nothing else precedes it.
+                        let docstring = syntax.docstrings && multiline && !code && !comment;
+                        if docstring {
+                            comment = true;
+                        } else {
+                            code = true;
+                        }
+                        rest = &rest[open.len()..];
+                        state = State::Str {
+                            close,
+                            multiline,
+                            docstring,
+                        };
+                        continue;
+                    }
+
+                    code = true;
+                    rest = skip_char(rest);
+                }
+            }
+        }
+
+        if let State::Str {
+            multiline: false, ..
+        } = state
+        {
+            state = State::Code;
+        }
+        if code {
+            counts.code += 1;
+        } else if comment {
+            counts.comment += 1;
+        } else {
+            counts.blank += 1;
+        }
+    }
+
+    counts
+}
+
+/// The longest delimiter pair whose opening delimiter starts `rest`.
+fn longest<'a>(pairs: &'a [(String, String)], rest: &str) -> Option<(&'a str, &'a str)> {
+    pairs
+        .iter()
+        .filter(|(open, _)| rest.starts_with(open.as_str()))
+        .max_by_key(|(open, _)| open.len())
+        .map(|(open, close)| (open.as_str(), close.as_str()))
+}
+
+/// The length of the character literal that starts `rest`, such as `'"'` or `'\''`. `None`
+/// for a `'` that starts none, like a Rust lifetime.
+fn char_literal(rest: &str) -> Option<usize> {
+    if !rest.starts_with('\'') {
+        return None;
+    }
+    let mut chars = rest.char_indices().skip(1);
+    match chars.next()? {
+        (_, '\'') => None,
+        (_, '\\') => {
+            chars.next()?;
+            chars.find(|&(_, c)| c == '\'').map(|(i, _)| i + 1)
+        }
+        _ => chars.next().filter(|&(_, c)| c == '\'').map(|(i, _)| i + 1),
+    }
+}
+
+/// The length of a raw string opener such as `r"`, `br#"` or `r##"`, and its number of `#`.
+fn raw_string_start(rest: &str) -> Option<(usize, usize)> {
+    let after_prefix = rest
+        .strip_prefix("br")
+        .or_else(|| rest.strip_prefix("cr"))
+        .or_else(|| rest.strip_prefix('r'))?;
+    let hashes = after_prefix.bytes().take_while(|&b| b == b'#').count();
+    after_prefix[hashes..]
+        .starts_with('"')
+        .then_some((rest.len() - after_prefix.len() + hashes + 1, hashes))
+}
+
+fn skip_char(s: &str) -> &str {
+    let mut chars = s.chars();
+    chars.next();
+    chars.as_str()
+}
@@ -1,4 +1,4 @@
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::PathBuf;
 
 #[derive(Debug, Default)]
@@ -6,6 +6,42 @@ pub struct DirData {
     pub name: String,
     pub children: BTreeSet<PathBuf>,
     pub direct_files: usize,
+    /// Direct file counts per canonical language name.
+    pub languages: BTreeMap<String, usize>,
+    /// Total size of the directory's own matched files.
+    pub bytes: u64,
+    /// Lines of the directory's own files; only filled in with `--lines`.
+    pub lines: LineCounts,
+    /// Part of the subtree was not scanned because of `--prune`.
+    pub partial: bool,
+}
+
+/// Totals for a directory and everything below it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Counts {
+    pub files: usize,
+    pub bytes: u64,
+    pub languages: BTreeMap<String, usize>,
+    pub lines: LineCounts,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LineCounts {
+    pub code: usize,
+    pub comment: usize,
+    pub blank: usize,
+}
+
+impl LineCounts {
+    pub fn total(&self) -> usize {
+        self.code + self.comment + self.blank
+    }
+
+    pub fn add(&mut self, other: LineCounts) {
+        self.code += other.code;
+        self.comment += other.comment;
+        self.blank += other.blank;
+    }
 }
 
 #[derive(Debug)]
@@ -13,5 +49,15 @@ pub struct ScanResult {
     pub root: PathBuf,
     pub dirs: HashMap<PathBuf, DirData>,
     pub total_files: usize,
+    pub total_bytes: u64,
     pub dirs_with_files: usize,
+    pub warnings: Vec<ScanWarning>,
+    pub skip_dirs: Vec<String>,
+}
+
+/// A path the scan had to skip because it could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanWarning {
+    pub path: PathBuf,
+    pub reason: String,
 }
@@ -1,28 +1,163 @@
-use std::collections::{HashMap, HashSet};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 
-use crate::cli::{Cli, CountMode};
-use crate::lang::display_langs;
-use crate::model::ScanResult;
+use crate::cli::{Cli, CountBy, CountMode, FlatSort, Format, HeaderMode, SortBy};
+use crate::json::Value;
+use crate::lang::{LanguageInfo, Selection, display_langs};
+use crate::model::{Counts, LineCounts, ScanResult};
 
 fn display_count(
     path: &Path,
     scan: &ScanResult,
-    tree_counts: &HashMap<PathBuf, usize>,
+    tree_counts: &HashMap<PathBuf, Counts>,
     mode: CountMode,
 ) -> usize {
     match mode {
         CountMode::Direct => scan.dirs.get(path).map_or(0, |d| d.direct_files),
-        CountMode::Tree => *tree_counts.get(path).unwrap_or(&0),
+        CountMode::Tree => tree_counts.get(path).map_or(0, |c| c.files),
     }
 }
 
+fn display_bytes(
+    path: &Path,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    mode: CountMode,
+) -> u64 {
+    match mode {
+        CountMode::Direct => scan.dirs.get(path).map_or(0, |d| d.bytes),
+        CountMode::Tree => tree_counts.get(path).map_or(0, |c| c.bytes),
+    }
+}
+
+/// The number `--count-by` selects, which `--min-count` is compared against.
+fn display_value(
+    path: &Path,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    cli: &Cli,
+) -> u64 {
+    match cli.count_by {
+        CountBy::Files => display_count(path, scan, tree_counts, cli.count_mode) as u64,
+        CountBy::Bytes => display_bytes(path, scan, tree_counts, cli.count_mode),
+    }
+}
+
+/// Formats a byte count with binary units, e.g. `512 B` or `1.5 MiB`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn display_languages<'a>(
+    path: &Path,
+    scan: &'a ScanResult,
+    tree_counts: &'a HashMap<PathBuf, Counts>,
+    mode: CountMode,
+) -> Option<&'a BTreeMap<String, usize>> {
+    match mode {
+        CountMode::Direct => scan.dirs.get(path).map(|d| &d.languages),
+        CountMode::Tree => tree_counts.get(path).map(|c| &c.languages),
+    }
+}
+
+/// Per-language counts such as `rust 40, python 3`, largest first.
+fn language_breakdown(languages: &BTreeMap<String, usize>) -> String {
+    let mut entries = languages
+        .iter()
+        .filter(|(_, n)| **n > 0)
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    entries
+        .iter()
+        .map(|(lang, n)| format!("{lang} {n}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The breakdown column of a text line.
+fn language_column(
+    path: &Path,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    mode: CountMode,
+) -> String {
+    node_breakdown(path, scan, tree_counts, mode).map_or(String::new(), |b| format!("  [{b}]"))
+}
+
+/// A node's language breakdown, if the scan found more than one language and the node has any.
+fn node_breakdown(
+    path: &Path,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    mode: CountMode,
+) -> Option<String> {
+    if tree_counts
+        .get(&scan.root)
+        .is_none_or(|c| c.languages.len() < 2)
+    {
+        return None;
+    }
+    display_languages(path, scan, tree_counts, mode)
+        .map(language_breakdown)
+        .filter(|b| !b.is_empty())
+}
+
+fn display_lines(
+    path: &Path,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    mode: CountMode,
+) -> LineCounts {
+    match mode {
+        CountMode::Direct => scan.dirs.get(path).map(|d| d.lines),
+        CountMode::Tree => tree_counts.get(path).map(|c| c.lines),
+    }
+    .unwrap_or_default()
+}
+
+/// Formats a count for text output, marking tree totals that are missing pruned subtrees.
+/// With `--lines`, the line breakdown follows the file count.
+fn display_count_text(
+    path: &Path,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    cli: &Cli,
+) -> String {
+    let mut text = match cli.count_by {
+        CountBy::Files => display_count(path, scan, tree_counts, cli.count_mode).to_string(),
+        CountBy::Bytes => format_bytes(display_bytes(path, scan, tree_counts, cli.count_mode)),
+    };
+    let partial = scan.dirs.get(path).is_some_and(|d| d.partial);
+    if partial && cli.count_mode == CountMode::Tree {
+        text.push('+');
+    }
+    if cli.lines {
+        let lines = display_lines(path, scan, tree_counts, cli.count_mode);
+        text.push_str(&format!(
+            ", {} code, {} comment, {} blank",
+            lines.code, lines.comment, lines.blank
+        ));
+    }
+    text
+}
+
 fn should_show_at_depth(
     path: &Path,
     depth: usize,
     scan: &ScanResult,
-    tree_counts: &HashMap<PathBuf, usize>,
+    tree_counts: &HashMap<PathBuf, Counts>,
     cli: &Cli,
 ) -> bool {
     if path == scan.root {
@@ -31,19 +166,20 @@ fn should_show_at_depth(
     if cli.show_empty {
         return true;
     }
-    let subtree = *tree_counts.get(path).unwrap_or(&0);
-    if subtree == 0 {
+    let subtree = tree_counts.get(path).map_or(0, |c| c.files);
+    let partial = scan.dirs.get(path).is_some_and(|d| d.partial);
+    if subtree == 0 && !partial {
         return false;
     }
 
-    let current = display_count(path, scan, tree_counts, cli.count_mode);
-    if current >= cli.min_count {
+    let current = display_value(path, scan, tree_counts, cli);
+    if current >= cli.min_count as u64 {
         return true;
     }
 
     if cli.max_depth.is_some_and(|max| depth >= max) {
         // We can't show deeper levels, so keep this node if it has any matching descendants.
-        return subtree > 0;
+        return subtree > 0 || partial;
     }
 
     scan.dirs.get(path).is_some_and(|d| {
@@ -53,10 +189,223 @@ fn should_show_at_depth(
     })
 }
 
+/// Every node the renderers draw with its depth, in tree order.
+fn visible_nodes(
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    cli: &Cli,
+) -> Vec<(PathBuf, usize)> {
+    fn walk(
+        path: &Path,
+        depth: usize,
+        scan: &ScanResult,
+        tree_counts: &HashMap<PathBuf, Counts>,
+        cli: &Cli,
+        out: &mut Vec<(PathBuf, usize)>,
+    ) {
+        out.push((path.to_path_buf(), depth));
+        for child in visible_children(path, depth, scan, tree_counts, cli) {
+            walk(&child, depth + 1, scan, tree_counts, cli, out);
+        }
+    }
+
+    let mut out = vec![];
+    walk(&scan.root, 0, scan, tree_counts, cli, &mut out);
+    out
+}
+
+/// The children of the node at `depth` that the renderers draw, in display order.
+fn visible_children(
+    path: &Path,
+    depth: usize,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    cli: &Cli,
+) -> Vec<PathBuf> {
+    if cli.max_depth.is_some_and(|max| depth >= max) {
+        return vec![];
+    }
+    let mut children = scan
+        .dirs
+        .get(path)
+        .map(|d| {
+            d.children
+                .iter()
+                .filter(|c| should_show_at_depth(c, depth + 1, scan, tree_counts, cli))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    // `children` comes out of a BTreeSet, already in name order.
+    match cli.sort {
+        SortBy::Name => {}
+        SortBy::Count => {
+            children.sort_by_key(|c| std::cmp::Reverse(display_value(c, scan, tree_counts, cli)))
+        }
+        SortBy::Natural => children.sort_by(|a, b| {
+            natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()).then_with(|| a.cmp(b))
+        }),
+    }
+    if cli.reverse {
+        children.reverse();
+    }
+    children
+}
+
+/// The visible children that `--top` leaves out, summed up.
+#[derive(Default)]
+struct Others {
+    dirs: usize,
+    files: usize,
+    bytes: u64,
+    languages: BTreeMap<String, usize>,
+    lines: LineCounts,
+    partial: bool,
+}
+
+impl Others {
+    /// `… (3 more dirs, 12 files)`, or the byte size with `--count-by bytes`.
+    fn label(&self, cli: &Cli) -> String {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let amount = match cli.count_by {
+            CountBy::Files => format!("{} file{}", self.files, plural(self.files)),
+            CountBy::Bytes => format_bytes(self.bytes),
+        };
+        let partial = if self.partial && cli.count_mode == CountMode::Tree {
+            "+"
+        } else {
+            ""
+        };
+        format!(
+            "… ({} more dir{}, {amount}{partial})",
+            self.dirs,
+            plural(self.dirs)
+        )
+    }
+}
+
+/// The visible children of a node, cut down to the `--top` largest ones (kept in display
+/// order), plus the rollup of the rest.
+fn top_children(
+    path: &Path,
+    depth: usize,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    cli: &Cli,
+) -> (Vec<PathBuf>, Option<Others>) {
+    let children = visible_children(path, depth, scan, tree_counts, cli);
+    let Some(top) = cli.top.filter(|n| children.len() > *n) else {
+        return (children, None);
+    };
+    let mut by_size = (0..children.len()).collect::<Vec<_>>();
+    by_size
+        .sort_by_key(|&i| std::cmp::Reverse(display_value(&children[i], scan, tree_counts, cli)));
+    let mut keep = vec![false; children.len()];
+    for &i in &by_size[..top] {
+        keep[i] = true;
+    }
+
+    let mut shown = vec![];
+    let mut others = Others::default();
+    for (child, keep) in children.into_iter().zip(keep) {
+        if keep {
+            shown.push(child);
+            continue;
+        }
+        others.dirs += 1;
+        others.files += display_count(&child, scan, tree_counts, cli.count_mode);
+        others.bytes += display_bytes(&child, scan, tree_counts, cli.count_mode);
+        for (lang, n) in display_languages(&child, scan, tree_counts, cli.count_mode)
+            .into_iter()
+            .flatten()
+        {
+            *others.languages.entry(lang.clone()).or_default() += n;
+        }
+        others
+            .lines
+            .add(display_lines(&child, scan, tree_counts, cli.count_mode));
+        others.partial |= scan.dirs.get(&child).is_some_and(|d| d.partial);
+    }
+    (shown, Some(others))
+}
+
+/// With `--collapse`, follows a chain of directories that each have exactly one visible
+/// child showing the same counts, e.g. `src/main/java`. Returns the deepest directory of the
+/// chain, its depth and the names along the way; without `--collapse` the chain is just `path`.
+fn collapse_chain(
+    path: &Path,
+    depth: usize,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    cli: &Cli,
+) -> (PathBuf, usize, Vec<String>) {
+    let name = |p: &Path| scan.dirs.get(p).map(|d| d.name.clone()).unwrap_or_default();
+    let mut last = path.to_path_buf();
+    let mut last_depth = depth;
+    let mut names = vec![name(path)];
+    if !cli.collapse {
+        return (last, last_depth, names);
+    }
+    // Every level of the chain is counted against --max-depth, so a chain never reaches
+    // deeper than the uncollapsed tree would.
+    loop {
+        let children = visible_children(&last, last_depth, scan, tree_counts, cli);
+        let [child] = children.as_slice() else {
+            break;
+        };
+        let same = display_count_text(&last, scan, tree_counts, cli)
+            == display_count_text(child, scan, tree_counts, cli)
+            && display_languages(&last, scan, tree_counts, cli.count_mode)
+                == display_languages(child, scan, tree_counts, cli.count_mode);
+        if !same {
+            break;
+        }
+        names.push(name(child));
+        last = child.clone();
+        last_depth += 1;
+    }
+    (last, last_depth, names)
+}
+
+/// Compares names the way people count: digit runs by value (`dir2` before `dir10`), the rest
+/// case-insensitively.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let order = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_lowercase().cmp(y.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
 pub fn render_text(
     scan: &ScanResult,
-    tree_counts: &HashMap<PathBuf, usize>,
-    extensions: &HashSet<String>,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    selection: &Selection,
     langs: &[String],
     cli: &Cli,
     duration_secs: f64,
@@ -67,47 +416,73 @@ pub fn render_text(
     out.push_str(&title);
     out.push('\n');
     out.push_str("============================================================\n");
-    let root_name = scan
-        .root
-        .file_name()
-        .unwrap_or_else(|| OsStr::new("."))
-        .to_string_lossy();
+    let root_name = root_name(scan);
     out.push_str(&format!(
-        "{root_name}/ ({})\n",
-        display_count(&scan.root, scan, tree_counts, cli.count_mode)
+        "{root_name}/ ({}){}\n",
+        display_count_text(&scan.root, scan, tree_counts, cli),
+        language_column(&scan.root, scan, tree_counts, cli.count_mode)
     ));
 
-    let children = scan
-        .dirs
-        .get(&scan.root)
-        .map(|d| d.children.iter().cloned().collect::<Vec<_>>())
-        .unwrap_or_default();
-    let visible = children
-        .into_iter()
-        .filter(|c| should_show_at_depth(c, 1, scan, tree_counts, cli))
-        .collect::<Vec<_>>();
-    for (idx, child) in visible.iter().enumerate() {
-        render_text_node(
-            &mut out,
-            child,
-            scan,
-            tree_counts,
-            cli,
-            "",
-            idx + 1 == visible.len(),
-            1,
-        );
-    }
+    render_text_children(&mut out, &scan.root, scan, tree_counts, cli, "", 0);
 
     out.push_str("============================================================\n");
     out.push_str(&format!("Total matching files: {}\n", scan.total_files));
+    out.push_str(&format!(
+        "Total size: {} ({} bytes)\n",
+        format_bytes(scan.total_bytes),
+        scan.total_bytes
+    ));
+    if let Some(max) = cli.max_depth.filter(|_| cli.prune) {
+        out.push_str(&format!(
+            "Not scanned below depth {max} (--prune); counts marked + are partial\n"
+        ));
+    }
     out.push_str(&format!(
         "Directories containing files: {}\n",
         scan.dirs_with_files
     ));
-    let mut exts = extensions.iter().cloned().collect::<Vec<_>>();
+    if cli.lines {
+        let lines = tree_counts
+            .get(&scan.root)
+            .map(|c| c.lines)
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "Total lines: {} ({} code, {} comment, {} blank)\n",
+            lines.total(),
+            lines.code,
+            lines.comment,
+            lines.blank
+        ));
+    }
+    if let Some(totals) = tree_counts
+        .get(&scan.root)
+        .filter(|c| !c.languages.is_empty())
+    {
+        out.push_str(&format!(
+            "Languages: {}\n",
+            language_breakdown(&totals.languages)
+        ));
+    }
+    let mut exts = selection.extensions.iter().cloned().collect::<Vec<_>>();
     exts.sort();
     out.push_str(&format!("Extensions: {}\n", exts.join(",")));
+    if !selection.excluded_extensions.is_empty() {
+        let mut excluded = selection
+            .excluded_extensions
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        excluded.sort();
+        out.push_str(&format!("Excluded extensions: {}\n", excluded.join(",")));
+    }
+    out.push_str(&format!(
+        "Skipped directories: {}\n",
+        if scan.skip_dirs.is_empty() {
+            "(none)".to_string()
+        } else {
+            scan.skip_dirs.join(",")
+        }
+    ));
 
     let files_per_sec = if duration_secs > 0.0 {
         scan.total_files as f64 / duration_secs
@@ -118,44 +493,59 @@ pub fn render_text(
         "Scan time: {:.2} s, {:.2} files/s\n",
         duration_secs, files_per_sec
     ));
+
+    if !scan.warnings.is_empty() {
+        out.push_str(&format!("Warnings ({}):\n", scan.warnings.len()));
+        for w in &scan.warnings {
+            out.push_str(&format!("  {}: {}\n", w.path.display(), w.reason));
+        }
+    }
     out
 }
 
+#[allow(clippy::too_many_arguments)]
 fn render_text_node(
     out: &mut String,
     path: &Path,
     scan: &ScanResult,
-    tree_counts: &HashMap<PathBuf, usize>,
+    tree_counts: &HashMap<PathBuf, Counts>,
     cli: &Cli,
     prefix: &str,
     is_last: bool,
     depth: usize,
 ) {
-    let Some(dir) = scan.dirs.get(path) else {
+    if !scan.dirs.contains_key(path) {
         return;
-    };
+    }
+    let (path, depth, names) = collapse_chain(path, depth, scan, tree_counts, cli);
+    let path = path.as_path();
     let connector = if is_last { "└── " } else { "├── " };
     out.push_str(&format!(
-        "{prefix}{connector}{}/ ({})\n",
-        dir.name,
-        display_count(path, scan, tree_counts, cli.count_mode)
+        "{prefix}{connector}{}/ ({}){}\n",
+        names.join("/"),
+        display_count_text(path, scan, tree_counts, cli),
+        language_column(path, scan, tree_counts, cli.count_mode)
     ));
 
-    if cli.max_depth.is_some_and(|max| depth >= max) {
-        return;
-    }
-
     let next_prefix = if is_last {
         format!("{prefix}    ")
     } else {
         format!("{prefix}│   ")
     };
-    let children = dir
-        .children
-        .iter()
-        .filter(|c| should_show_at_depth(c, depth + 1, scan, tree_counts, cli))
-        .cloned()
-        .collect::<Vec<_>>();
+    render_text_children(out, path, scan, tree_counts, cli, &next_prefix, depth);
+}
+
+/// Draws the children of the node at `depth`, ending with the `--top` rollup line if any.
+fn render_text_children(
+    out: &mut String,
+    path: &Path,
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    cli: &Cli,
+    prefix: &str,
+    depth: usize,
+) {
+    let (children, others) = top_children(path, depth, scan, tree_counts, cli);
     for (idx, child) in children.iter().enumerate() {
         render_text_node(
             out,
@@ -163,148 +553,1111 @@ fn render_text_node(
             scan,
             tree_counts,
             cli,
-            &next_prefix,
-            idx + 1 == children.len(),
+            prefix,
+            idx + 1 == children.len() && others.is_none(),
             depth + 1,
         );
     }
+    if let Some(others) = others {
+        out.push_str(&format!("{prefix}└── {}\n", others.label(cli)));
+    }
 }
 
-pub fn render_json(
+/// The summary statistics of a report as label/value pairs, in the order the text output
+/// prints them.
+fn summary_rows(
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    selection: &Selection,
+    cli: &Cli,
+    duration_secs: f64,
+) -> Vec<(&'static str, String)> {
+    let totals = tree_counts.get(&scan.root);
+    let mut rows = vec![
+        ("Total matching files", scan.total_files.to_string()),
+        (
+            "Total size",
+            format!(
+                "{} ({} bytes)",
+                format_bytes(scan.total_bytes),
+                scan.total_bytes
+            ),
+        ),
+    ];
+    if let Some(max) = cli.max_depth.filter(|_| cli.prune) {
+        rows.push((
+            "Not scanned below depth",
+            format!("{max} (--prune); counts marked + are partial"),
+        ));
+    }
+    rows.push((
+        "Directories containing files",
+        scan.dirs_with_files.to_string(),
+    ));
+    if cli.lines {
+        let lines = totals.map(|c| c.lines).unwrap_or_default();
+        rows.push((
+            "Total lines",
+            format!(
+                "{} ({} code, {} comment, {} blank)",
+                lines.total(),
+                lines.code,
+                lines.comment,
+                lines.blank
+            ),
+        ));
+    }
+    if let Some(totals) = totals.filter(|c| !c.languages.is_empty()) {
+        rows.push(("Languages", language_breakdown(&totals.languages)));
+    }
+    rows.push(("Extensions", sorted(&selection.extensions).join(",")));
+    if !selection.excluded_extensions.is_empty() {
+        rows.push((
+            "Excluded extensions",
+            sorted(&selection.excluded_extensions).join(","),
+        ));
+    }
+    rows.push((
+        "Skipped directories",
+        if scan.skip_dirs.is_empty() {
+            "(none)".to_string()
+        } else {
+            scan.skip_dirs.join(",")
+        },
+    ));
+    rows.push(("Scan time", format!("{duration_secs:.2} s")));
+    rows
+}
+
+pub fn render_markdown(
     scan: &ScanResult,
-    tree_counts: &HashMap<PathBuf, usize>,
-    extensions: &HashSet<String>,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    selection: &Selection,
     langs: &[String],
     cli: &Cli,
-    _duration_secs: f64,
-    pretty: bool,
+    duration_secs: f64,
 ) -> String {
-    fn node(
+    fn item(
+        out: &mut String,
         path: &Path,
+        depth: usize,
         scan: &ScanResult,
-        tree_counts: &HashMap<PathBuf, usize>,
+        tree_counts: &HashMap<PathBuf, Counts>,
         cli: &Cli,
-        pretty: bool,
-        indent: usize,
-        depth: usize,
-    ) -> String {
-        let d = scan.dirs.get(path).expect("node exists");
-        let children = if cli.max_depth.is_some_and(|max| depth >= max) {
-            vec![]
+    ) {
+        let Some(dir) = scan.dirs.get(path) else {
+            return;
+        };
+        let name = if path == scan.root {
+            root_name(scan)
         } else {
-            d.children
-                .iter()
-                .filter(|c| should_show_at_depth(c, depth + 1, scan, tree_counts, cli))
-                .cloned()
-                .collect::<Vec<_>>()
+            dir.name.clone()
         };
+        out.push_str(&format!(
+            "{}- {} ({})",
+            "  ".repeat(depth),
+            markdown_code(&format!("{name}/")),
+            markdown_text(&display_count_text(path, scan, tree_counts, cli))
+        ));
+        if let Some(breakdown) = node_breakdown(path, scan, tree_counts, cli.count_mode) {
+            out.push_str(&format!(" — {}", markdown_text(&breakdown)));
+        }
+        out.push('\n');
+        for child in visible_children(path, depth, scan, tree_counts, cli) {
+            item(out, &child, depth + 1, scan, tree_counts, cli);
+        }
+    }
 
-        let mut child_json = vec![];
-        for child in &children {
-            child_json.push(node(
-                child,
-                scan,
-                tree_counts,
-                cli,
-                pretty,
-                indent + 2,
-                depth + 1,
+    let mut out = format!(
+        "# {} file statistics\n\n",
+        markdown_text(&display_langs(langs))
+    );
+    item(&mut out, &scan.root, 0, scan, tree_counts, cli);
+
+    out.push_str("\n## Summary\n\n| Metric | Value |\n| --- | --- |\n");
+    for (label, value) in summary_rows(scan, tree_counts, selection, cli, duration_secs) {
+        out.push_str(&format!("| {label} | {} |\n", markdown_text(&value)));
+    }
+
+    if !scan.warnings.is_empty() {
+        out.push_str(&format!("\n## Warnings ({})\n\n", scan.warnings.len()));
+        for w in &scan.warnings {
+            out.push_str(&format!(
+                "- {}: {}\n",
+                markdown_code(&w.path.to_string_lossy()),
+                markdown_text(&w.reason)
+            ));
+        }
+    }
+    out
+}
+
+/// A single-file HTML report: the visible tree as a collapsible, sortable, searchable table
+/// (script and styles inlined from `data/report.html`) and the summary statistics.
+pub fn render_html(
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    selection: &Selection,
+    langs: &[String],
+    cli: &Cli,
+    duration_secs: f64,
+) -> String {
+    fn node(
+        path: &Path,
+        depth: usize,
+        scan: &ScanResult,
+        tree_counts: &HashMap<PathBuf, Counts>,
+        cli: &Cli,
+    ) -> Value {
+        let name = if path == scan.root {
+            root_name(scan)
+        } else {
+            scan.dirs
+                .get(path)
+                .map(|d| d.name.clone())
+                .unwrap_or_default()
+        };
+        let bytes = display_bytes(path, scan, tree_counts, cli.count_mode);
+        let mut fields = vec![
+            ("name", Value::from(name)),
+            ("path", relative_path(path, scan).into()),
+            (
+                "files",
+                display_count(path, scan, tree_counts, cli.count_mode).into(),
+            ),
+            ("bytes", bytes.into()),
+            ("size", format_bytes(bytes).into()),
+            (
+                "languages",
+                json_count_map(display_languages(path, scan, tree_counts, cli.count_mode)),
+            ),
+            (
+                "breakdown",
+                node_breakdown(path, scan, tree_counts, cli.count_mode)
+                    .unwrap_or_default()
+                    .into(),
+            ),
+            (
+                "partial",
+                scan.dirs.get(path).is_some_and(|d| d.partial).into(),
+            ),
+        ];
+        if cli.lines {
+            fields.push((
+                "lines",
+                json_lines(display_lines(path, scan, tree_counts, cli.count_mode)),
             ));
         }
-        let pad = if pretty {
-            " ".repeat(indent)
+        let children = visible_children(path, depth, scan, tree_counts, cli)
+            .iter()
+            .map(|c| node(c, depth + 1, scan, tree_counts, cli))
+            .collect();
+        fields.push(("children", Value::Array(children)));
+        Value::object(fields)
+    }
+
+    let title = format!("{} file statistics", display_langs(langs));
+    let mut columns = vec![
+        ("name", "Directory", false),
+        ("files", "Files", true),
+        ("bytes", "Size", true),
+    ];
+    if cli.lines {
+        columns.extend([
+            ("code", "Code", true),
+            ("comment", "Comment", true),
+            ("blank", "Blank", true),
+        ]);
+    }
+    columns.push(("languages", "Languages", false));
+    let columns = columns
+        .iter()
+        .map(|(key, label, num)| {
+            let class = if *num { " class=\"num\"" } else { "" };
+            format!("<th data-key=\"{key}\"{class}>{label}</th>")
+        })
+        .collect::<String>();
+    let summary = summary_rows(scan, tree_counts, selection, cli, duration_secs)
+        .iter()
+        .map(|(label, value)| format!("<tr><th>{label}</th><td>{}</td></tr>", html_escape(value)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let warnings = if scan.warnings.is_empty() {
+        String::new()
+    } else {
+        let items = scan
+            .warnings
+            .iter()
+            .map(|w| {
+                format!(
+                    "<li><code>{}</code>: {}</li>",
+                    html_escape(&w.path.to_string_lossy()),
+                    html_escape(&w.reason)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            "<h2>Warnings ({})</h2>\n<ul>\n{items}\n</ul>",
+            scan.warnings.len()
+        )
+    };
+    // `<` only occurs inside JSON strings, where `\u003c` keeps `</script>` from ending the block.
+    let data = node(&scan.root, 0, scan, tree_counts, cli)
+        .to_json(false)
+        .replace('<', "\\u003c");
+
+    fill_template(
+        include_str!("../data/report.html"),
+        &[
+            ("title", &html_escape(&title)),
+            ("columns", &columns),
+            ("summary", &summary),
+            ("warnings", &warnings),
+            ("data", &data),
+        ],
+    )
+}
+
+const TREEMAP_WIDTH: f64 = 1200.0;
+const TREEMAP_HEIGHT: f64 = 800.0;
+const TREEMAP_HEADER: f64 = 16.0;
+const TREEMAP_PADDING: f64 = 2.0;
+const TREEMAP_COLORS: [&str; 6] = [
+    "#4e79a7", "#59a14f", "#edc948", "#e15759", "#76b7b2", "#b07aa1",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+/// Splits `rect` into one rectangle per weight with the squarified layout of Bruls, Huizing
+/// and van Wijk. Weights should be sorted largest first; rectangles come back in the same order.
+pub fn squarify(weights: &[f64], rect: Rect) -> Vec<Rect> {
+    fn worst(row: &[f64], short: f64) -> f64 {
+        let sum = row.iter().sum::<f64>();
+        row.iter()
+            .map(|&a| (short * short * a / (sum * sum)).max(sum * sum / (short * short * a)))
+            .fold(0.0, f64::max)
+    }
+
+    let total = weights.iter().sum::<f64>();
+    if total <= 0.0 {
+        return weights
+            .iter()
+            .map(|_| Rect {
+                w: 0.0,
+                h: 0.0,
+                ..rect
+            })
+            .collect();
+    }
+    let scale = rect.w * rect.h / total;
+    let areas = weights.iter().map(|w| w * scale).collect::<Vec<_>>();
+    let mut rects = Vec::with_capacity(areas.len());
+    let mut free = rect;
+    let mut start = 0;
+    while start < areas.len() {
+        let short = free.w.min(free.h);
+        let mut end = start + 1;
+        while end < areas.len()
+            && worst(&areas[start..=end], short) <= worst(&areas[start..end], short)
+        {
+            end += 1;
+        }
+        let row = &areas[start..end];
+        let sum = row.iter().sum::<f64>();
+        if free.w >= free.h {
+            // A column along the left edge.
+            let thickness = if free.h > 0.0 { sum / free.h } else { 0.0 };
+            let mut y = free.y;
+            for a in row {
+                let h = if thickness > 0.0 { a / thickness } else { 0.0 };
+                rects.push(Rect {
+                    x: free.x,
+                    y,
+                    w: thickness,
+                    h,
+                });
+                y += h;
+            }
+            free.x += thickness;
+            free.w -= thickness;
         } else {
-            String::new()
+            // A row along the top edge.
+            let thickness = if free.w > 0.0 { sum / free.w } else { 0.0 };
+            let mut x = free.x;
+            for a in row {
+                let w = if thickness > 0.0 { a / thickness } else { 0.0 };
+                rects.push(Rect {
+                    x,
+                    y: free.y,
+                    w,
+                    h: thickness,
+                });
+                x += w;
+            }
+            free.y += thickness;
+            free.h -= thickness;
+        }
+        start = end;
+    }
+    rects
+}
+
+/// The subtree total that sizes treemap rectangles and shades graph nodes: bytes with
+/// `--count-by bytes`, lines with `--lines`, files otherwise.
+fn tree_weight(path: &Path, tree_counts: &HashMap<PathBuf, Counts>, cli: &Cli) -> u64 {
+    let counts = tree_counts.get(path);
+    match cli.count_by {
+        CountBy::Bytes => counts.map_or(0, |c| c.bytes),
+        CountBy::Files if cli.lines => counts.map_or(0, |c| c.lines.total()) as u64,
+        CountBy::Files => counts.map_or(0, |c| c.files) as u64,
+    }
+}
+
+pub fn render_svg_treemap(
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    langs: &[String],
+    cli: &Cli,
+) -> String {
+    #[allow(clippy::too_many_arguments)]
+    fn draw(
+        out: &mut String,
+        path: &Path,
+        rect: Rect,
+        depth: usize,
+        scan: &ScanResult,
+        tree_counts: &HashMap<PathBuf, Counts>,
+        cli: &Cli,
+        unit: &str,
+    ) {
+        if rect.w < 1.0 || rect.h < 1.0 {
+            return;
+        }
+        let name = if path == scan.root {
+            root_name(scan)
+        } else {
+            scan.dirs
+                .get(path)
+                .map(|d| d.name.clone())
+                .unwrap_or_default()
+        };
+        let weight = tree_weight(path, tree_counts, cli);
+        let amount = if cli.count_by == CountBy::Bytes {
+            format_bytes(weight)
+        } else {
+            format!("{weight} {unit}")
+        };
+        out.push_str(&format!(
+            "<g><title>{} ({})</title>\n<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>\n",
+            html_escape(&if path == scan.root {
+                name.clone()
+            } else {
+                relative_path(path, scan)
+            }),
+            html_escape(&amount),
+            rect.x,
+            rect.y,
+            rect.w,
+            rect.h,
+            TREEMAP_COLORS[depth % TREEMAP_COLORS.len()]
+        ));
+        // Roughly 7 pixels per character at the 12px label size.
+        let fits = ((rect.w - 6.0) / 7.0).max(0.0) as usize;
+        if rect.h >= 14.0 && fits >= 2 {
+            let label = format!("{name}/ ({amount})");
+            let label = if label.chars().count() > fits {
+                let mut short = label.chars().take(fits - 1).collect::<String>();
+                short.push('…');
+                short
+            } else {
+                label
+            };
+            out.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>\n",
+                rect.x + 3.0,
+                rect.y + 12.0,
+                html_escape(&label)
+            ));
+        }
+        out.push_str("</g>\n");
+
+        let inner = Rect {
+            x: rect.x + TREEMAP_PADDING,
+            y: rect.y + TREEMAP_HEADER,
+            w: rect.w - 2.0 * TREEMAP_PADDING,
+            h: rect.h - TREEMAP_HEADER - TREEMAP_PADDING,
         };
-        let sep = if pretty { "\n" } else { "" };
-        let inner = if pretty {
-            " ".repeat(indent + 2)
+        if inner.w < 4.0 || inner.h < 4.0 {
+            return;
+        }
+        let mut children = visible_children(path, depth, scan, tree_counts, cli)
+            .into_iter()
+            .map(|c| (tree_weight(&c, tree_counts, cli), Some(c)))
+            .filter(|(w, _)| *w > 0)
+            .collect::<Vec<_>>();
+        if children.is_empty() {
+            return;
+        }
+        // The directory's own files and hidden children keep their share of the area, undrawn.
+        let shown = children.iter().map(|(w, _)| w).sum::<u64>();
+        if weight > shown {
+            children.push((weight - shown, None));
+        }
+        children.sort_by_key(|(w, _)| std::cmp::Reverse(*w));
+        let weights = children.iter().map(|(w, _)| *w as f64).collect::<Vec<_>>();
+        for ((_, child), rect) in children.iter().zip(squarify(&weights, inner)) {
+            if let Some(child) = child {
+                draw(out, child, rect, depth + 1, scan, tree_counts, cli, unit);
+            }
+        }
+    }
+
+    let unit = match cli.count_by {
+        CountBy::Bytes => "bytes",
+        CountBy::Files if cli.lines => "lines",
+        CountBy::Files => "files",
+    };
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{TREEMAP_WIDTH}\" height=\"{TREEMAP_HEIGHT}\" viewBox=\"0 0 {TREEMAP_WIDTH} {TREEMAP_HEIGHT}\" font-family=\"sans-serif\" font-size=\"12\">\n<title>{} treemap by {unit}</title>\n<style>rect {{ stroke: #fff; stroke-width: 1; fill-opacity: 0.85; }} text {{ fill: #fff; pointer-events: none; }}</style>\n",
+        html_escape(&display_langs(langs))
+    );
+    draw(
+        &mut out,
+        &scan.root,
+        Rect {
+            x: 0.0,
+            y: 0.0,
+            w: TREEMAP_WIDTH,
+            h: TREEMAP_HEIGHT,
+        },
+        0,
+        scan,
+        tree_counts,
+        cli,
+        unit,
+    );
+    out.push_str("</svg>\n");
+    out
+}
+
+/// A Graphviz digraph of the visible directories. Nodes get darker and larger with their
+/// subtree total, relative to the root.
+pub fn render_dot(
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    langs: &[String],
+    cli: &Cli,
+) -> String {
+    #[allow(clippy::too_many_arguments)]
+    fn node(
+        out: &mut String,
+        path: &Path,
+        depth: usize,
+        next_id: &mut usize,
+        scan: &ScanResult,
+        tree_counts: &HashMap<PathBuf, Counts>,
+        cli: &Cli,
+        max_weight: u64,
+    ) {
+        let id = *next_id;
+        *next_id += 1;
+        let name = if path == scan.root {
+            root_name(scan)
         } else {
-            String::new()
+            scan.dirs
+                .get(path)
+                .map(|d| d.name.clone())
+                .unwrap_or_default()
         };
-        let children_str = if child_json.is_empty() {
-            "[]".to_string()
-        } else if pretty {
-            format!("[\n{}\n{}]", child_json.join(",\n"), inner)
+        let mut label = format!(
+            "{name}/ ({})",
+            display_count_text(path, scan, tree_counts, cli)
+        );
+        if let Some(breakdown) = node_breakdown(path, scan, tree_counts, cli.count_mode) {
+            label.push_str(&format!("\n{breakdown}"));
+        }
+        // Log scale, so one huge directory does not wash out the rest.
+        let weight = tree_weight(path, tree_counts, cli);
+        let shade = if max_weight > 0 {
+            ((weight as f64).ln_1p() / (max_weight as f64).ln_1p()).clamp(0.0, 1.0)
         } else {
-            format!("[{}]", child_json.join(","))
+            0.0
         };
+        let channel = |light: f64, dark: f64| (light + (dark - light) * shade).round() as u8;
+        out.push_str(&format!(
+            "  n{id} [label={}, tooltip={}, fillcolor=\"#{:02x}{:02x}{:02x}\", fontcolor=\"{}\", fontsize={:.1}];\n",
+            dot_string(&label),
+            dot_string(&if path == scan.root {
+                name.clone()
+            } else {
+                relative_path(path, scan)
+            }),
+            channel(239.0, 8.0),
+            channel(243.0, 69.0),
+            channel(255.0, 148.0),
+            if shade > 0.5 { "white" } else { "black" },
+            10.0 + 8.0 * shade
+        ));
+        for child in visible_children(path, depth, scan, tree_counts, cli) {
+            out.push_str(&format!("  n{id} -> n{};\n", *next_id));
+            node(
+                out,
+                &child,
+                depth + 1,
+                next_id,
+                scan,
+                tree_counts,
+                cli,
+                max_weight,
+            );
+        }
+    }
 
-        format!(
-            "{pad}{{{sep}{inner}\"name\":\"{}\",{sep}{inner}\"path\":\"{}\",{sep}{inner}\"files\":{},\n{inner}\"children\":{}{sep}{pad}}}",
-            escape_json(&d.name),
-            escape_json(&path.to_string_lossy()),
-            display_count(path, scan, tree_counts, cli.count_mode),
-            children_str
-        )
+    let mut out = String::from("digraph treestat {\n");
+    out.push_str(&format!(
+        "  graph [rankdir=LR, label={}, labelloc=t];\n",
+        dot_string(&format!("{} file statistics", display_langs(langs)))
+    ));
+    out.push_str("  node [shape=box, style=\"rounded,filled\", fontname=\"sans-serif\"];\n");
+    let max_weight = tree_weight(&scan.root, tree_counts, cli);
+    node(
+        &mut out,
+        &scan.root,
+        0,
+        &mut 0,
+        scan,
+        tree_counts,
+        cli,
+        max_weight,
+    );
+    out.push_str("}\n");
+    out
+}
+
+/// A double-quoted DOT string. `\n` inside `text` is kept as a DOT line break.
+fn dot_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => out.push('\u{fffd}'),
+            c => out.push(c),
+        }
     }
+    out.push('"');
+    out
+}
 
-    let mut exts = extensions.iter().cloned().collect::<Vec<_>>();
-    exts.sort();
-    let ext_json = if pretty {
-        exts.iter()
-            .map(|e| format!("\"{}\"", escape_json(e)))
-            .collect::<Vec<_>>()
-            .join(", ")
+/// Replaces each `{{key}}` in one pass, so substituted text is never expanded again.
+fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let key = &rest[start + 2..start + len];
+        match values.iter().find(|(k, _)| *k == key) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[start..start + len + 2]),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Escapes text for HTML and SVG. Control characters, which XML does not allow, become U+FFFD.
+fn html_escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            c if c.is_control() => out.push('\u{fffd}'),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escapes text so Markdown renders it literally, also inside table cells.
+fn markdown_text(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~' | '&' | '#' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if c.is_control() => out.push('\u{fffd}'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Wraps text in a code span, using a backtick fence longer than any run inside it.
+fn markdown_code(s: &str) -> String {
+    let s = s
+        .chars()
+        .map(|c| if c.is_control() { '\u{fffd}' } else { c })
+        .collect::<String>();
+    let longest = s.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    if s.starts_with('`') || s.ends_with('`') {
+        format!("{fence} {s} {fence}")
     } else {
-        exts.iter()
-            .map(|e| format!("\"{}\"", escape_json(e)))
-            .collect::<Vec<_>>()
-            .join(",")
-    };
-    let lang_str = display_langs(langs);
+        format!("{fence}{s}{fence}")
+    }
+}
 
-    if pretty {
-        format!(
-            "{{\n  \"root\": \"{}\",\n  \"path\": \"{}\",\n  \"count_mode\": \"{}\",\n  \"lang\": \"{}\",\n  \"extensions\": [{}],\n  \"max_depth\": {},\n  \"total_files\": {},\n  \"dirs_with_files\": {},\n  \"tree\": {}\n}}",
-            escape_json(
-                &scan
-                    .root
-                    .file_name()
-                    .unwrap_or_else(|| OsStr::new("."))
-                    .to_string_lossy()
+/// A path relative to the scan root, which itself is `.`.
+fn relative_path(path: &Path, scan: &ScanResult) -> String {
+    match path.strip_prefix(&scan.root) {
+        Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) => relative.to_string_lossy().into_owned(),
+        Err(_) => path.to_string_lossy().into_owned(),
+    }
+}
+
+fn root_name(scan: &ScanResult) -> String {
+    scan.root
+        .file_name()
+        .unwrap_or_else(|| OsStr::new("."))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// `du`-style `path<TAB>count` lines for every visible directory, ordered by path (byte order)
+/// or by count. The count is the number `--count-by` and `--count-mode` select; `--null` ends
+/// lines with NUL instead of newline.
+pub fn render_flat(scan: &ScanResult, tree_counts: &HashMap<PathBuf, Counts>, cli: &Cli) -> String {
+    let mut entries = visible_nodes(scan, tree_counts, cli)
+        .into_iter()
+        .map(|(path, _)| {
+            (
+                relative_path(&path, scan),
+                display_value(&path, scan, tree_counts, cli),
+            )
+        })
+        .collect::<Vec<_>>();
+    // A global order, independent of --sort, so the output can be checked with `sort -c`.
+    match cli.flat_sort {
+        FlatSort::Path => entries.sort(),
+        FlatSort::Count => entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0))),
+    }
+    if cli.reverse {
+        entries.reverse();
+    }
+    let terminator = if cli.null { '\0' } else { '\n' };
+    let mut out = String::new();
+    for (path, count) in entries {
+        out.push_str(&format!("{path}\t{count}{terminator}"));
+    }
+    out
+}
+
+/// One row per visible directory, separated by `,` (CSV) or a tab (TSV). Language and line
+/// columns follow `--count-mode`; file and byte counts are given both ways.
+pub fn render_table(
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    cli: &Cli,
+    separator: char,
+) -> String {
+    let languages = tree_counts
+        .get(&scan.root)
+        .map(|c| c.languages.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut header = vec![
+        "path",
+        "depth",
+        "direct_files",
+        "tree_files",
+        "direct_bytes",
+        "tree_bytes",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect::<Vec<_>>();
+    if cli.prune {
+        header.push("partial".to_string());
+    }
+    if cli.lines {
+        header.extend(["code", "comment", "blank"].map(String::from));
+    }
+    header.extend(languages.iter().cloned());
+
+    let mut out = String::new();
+    push_row(&mut out, &header, separator);
+    for (path, depth) in visible_nodes(scan, tree_counts, cli) {
+        let mut row = vec![
+            relative_path(&path, scan),
+            depth.to_string(),
+            display_count(&path, scan, tree_counts, CountMode::Direct).to_string(),
+            display_count(&path, scan, tree_counts, CountMode::Tree).to_string(),
+            display_bytes(&path, scan, tree_counts, CountMode::Direct).to_string(),
+            display_bytes(&path, scan, tree_counts, CountMode::Tree).to_string(),
+        ];
+        if cli.prune {
+            let partial = scan.dirs.get(&path).is_some_and(|d| d.partial);
+            row.push(partial.to_string());
+        }
+        if cli.lines {
+            let lines = display_lines(&path, scan, tree_counts, cli.count_mode);
+            row.extend([lines.code, lines.comment, lines.blank].map(|n| n.to_string()));
+        }
+        let counts = display_languages(&path, scan, tree_counts, cli.count_mode);
+        row.extend(languages.iter().map(|lang| {
+            counts
+                .and_then(|c| c.get(lang))
+                .copied()
+                .unwrap_or(0)
+                .to_string()
+        }));
+        push_row(&mut out, &row, separator);
+    }
+    out
+}
+
+fn push_row(out: &mut String, fields: &[String], separator: char) {
+    for (idx, field) in fields.iter().enumerate() {
+        if idx > 0 {
+            out.push(separator);
+        }
+        if separator == '\t' {
+            // TSV has no quoting, so use the usual backslash escapes.
+            for c in field.chars() {
+                match c {
+                    '\\' => out.push_str("\\\\"),
+                    '\t' => out.push_str("\\t"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    c => out.push(c),
+                }
+            }
+        } else if field.contains([separator, '"', '\n', '\r']) {
+            // RFC 4180: quote the field and double any quotes inside it.
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push('\n');
+}
+
+/// Version of the JSON output layout described by `schema/treestat.schema.json`. Bumped
+/// when a field is removed or changes meaning; new fields do not bump it.
+pub const JSON_SCHEMA_VERSION: u64 = 1;
+
+pub fn render_json(
+    scan: &ScanResult,
+    tree_counts: &HashMap<PathBuf, Counts>,
+    selection: &Selection,
+    langs: &[String],
+    cli: &Cli,
+    duration_secs: f64,
+    pretty: bool,
+) -> String {
+    fn node(
+        path: &Path,
+        scan: &ScanResult,
+        tree_counts: &HashMap<PathBuf, Counts>,
+        cli: &Cli,
+        depth: usize,
+    ) -> Value {
+        // The root is never collapsed, so the tree keeps a single, named top node.
+        let (path, depth, names) = if path == scan.root {
+            (path.to_path_buf(), depth, vec![])
+        } else {
+            collapse_chain(path, depth, scan, tree_counts, cli)
+        };
+        let path = path.as_path();
+        let d = scan.dirs.get(path).expect("node exists");
+        let (children, others) = top_children(path, depth, scan, tree_counts, cli);
+        let mut children = children
+            .iter()
+            .map(|c| node(c, scan, tree_counts, cli, depth + 1))
+            .collect::<Vec<_>>();
+        if let Some(others) = others {
+            let mut fields = vec![
+                ("name", Value::from(others.label(cli))),
+                ("path", Value::Null),
+                ("files", others.files.into()),
+                ("bytes", others.bytes.into()),
+                ("languages", json_count_map(Some(&others.languages))),
+            ];
+            if cli.lines {
+                fields.push(("lines", json_lines(others.lines)));
+            }
+            if others.partial {
+                fields.push(("partial", true.into()));
+            }
+            fields.push(("synthetic", true.into()));
+            fields.push(("dirs", others.dirs.into()));
+            fields.push(("children", Value::Array(vec![])));
+            children.push(Value::object(fields));
+        }
+
+        let mut fields = vec![
+            (
+                "name",
+                Value::from(if names.len() > 1 {
+                    names.join("/")
+                } else {
+                    d.name.clone()
+                }),
             ),
-            escape_json(&scan.root.to_string_lossy()),
-            match cli.count_mode {
-                CountMode::Direct => "direct",
-                CountMode::Tree => "tree",
-            },
-            lang_str,
-            ext_json,
-            cli.max_depth
-                .map(|v| v.to_string())
-                .unwrap_or_else(|| "null".to_string()),
-            scan.total_files,
-            scan.dirs_with_files,
-            node(&scan.root, scan, tree_counts, cli, true, 2, 0)
-        )
-    } else {
-        format!(
-            "{{\"root\":\"{}\",\"path\":\"{}\",\"count_mode\":\"{}\",\"lang\":\"{}\",\"extensions\":[{}],\"max_depth\":{},\"total_files\":{},\"dirs_with_files\":{},\"tree\":{}}}",
-            escape_json(
-                &scan
-                    .root
-                    .file_name()
-                    .unwrap_or_else(|| OsStr::new("."))
-                    .to_string_lossy()
+            ("path", path.to_string_lossy().as_ref().into()),
+            (
+                "files",
+                display_count(path, scan, tree_counts, cli.count_mode).into(),
+            ),
+            (
+                "bytes",
+                display_bytes(path, scan, tree_counts, cli.count_mode).into(),
+            ),
+            (
+                "languages",
+                json_count_map(display_languages(path, scan, tree_counts, cli.count_mode)),
             ),
-            escape_json(&scan.root.to_string_lossy()),
+        ];
+        if cli.lines {
+            fields.push((
+                "lines",
+                json_lines(display_lines(path, scan, tree_counts, cli.count_mode)),
+            ));
+        }
+        if d.partial {
+            fields.push(("partial", true.into()));
+        }
+        if names.len() > 1 {
+            fields.push(("collapsed", names.into()));
+        }
+        fields.push(("children", Value::Array(children)));
+        Value::object(fields)
+    }
+
+    let root_name = root_name(scan);
+    let totals = tree_counts.get(&scan.root);
+    let options = Value::object([
+        ("exclude", Value::from(cli.exclude.clone())),
+        ("hidden", cli.hidden.into()),
+        ("gitignore", (!cli.no_gitignore).into()),
+        (
+            "git_excludes",
+            (!cli.no_gitignore && !cli.no_git_excludes).into(),
+        ),
+        ("follow_symlinks", cli.follow_symlinks.into()),
+        (
+            "headers",
+            match cli.headers {
+                HeaderMode::Include => "include",
+                HeaderMode::Exclude => "exclude",
+                HeaderMode::Only => "only",
+            }
+            .into(),
+        ),
+        ("min_count", cli.min_count.into()),
+        ("show_empty", cli.show_empty.into()),
+        ("prune", cli.prune.into()),
+        ("lines", cli.lines.into()),
+        ("strict", cli.strict.into()),
+        ("threads", cli.threads.into()),
+        (
+            "languages_files",
+            cli.languages_files
+                .iter()
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .into(),
+        ),
+    ]);
+    let mut fields = vec![
+        ("schema_version", Value::from(JSON_SCHEMA_VERSION)),
+        ("root", Value::from(root_name.as_ref())),
+        ("path", scan.root.to_string_lossy().as_ref().into()),
+        (
+            "count_mode",
             match cli.count_mode {
                 CountMode::Direct => "direct",
                 CountMode::Tree => "tree",
-            },
-            lang_str,
-            ext_json,
-            cli.max_depth
-                .map(|v| v.to_string())
-                .unwrap_or_else(|| "null".to_string()),
-            scan.total_files,
-            scan.dirs_with_files,
-            node(&scan.root, scan, tree_counts, cli, false, 0, 0)
-        )
+            }
+            .into(),
+        ),
+        (
+            "count_by",
+            match cli.count_by {
+                CountBy::Files => "files",
+                CountBy::Bytes => "bytes",
+            }
+            .into(),
+        ),
+        ("lang", display_langs(langs).into()),
+        ("extensions", sorted(&selection.extensions).into()),
+        (
+            "excluded_extensions",
+            sorted(&selection.excluded_extensions).into(),
+        ),
+        ("skip_dirs", scan.skip_dirs.clone().into()),
+        ("max_depth", cli.max_depth.into()),
+        ("options", options),
+        ("total_files", scan.total_files.into()),
+        ("total_bytes", scan.total_bytes.into()),
+        ("dirs_with_files", scan.dirs_with_files.into()),
+        ("languages", json_count_map(totals.map(|c| &c.languages))),
+    ];
+    if cli.lines {
+        fields.push((
+            "lines",
+            json_lines(totals.map(|c| c.lines).unwrap_or_default()),
+        ));
     }
+    fields.push(("scan_time_secs", duration_secs.into()));
+    fields.push((
+        "warnings",
+        Value::Array(
+            scan.warnings
+                .iter()
+                .map(|w| {
+                    Value::object([
+                        ("path", w.path.to_string_lossy().as_ref().into()),
+                        ("reason", w.reason.as_str().into()),
+                    ])
+                })
+                .collect(),
+        ),
+    ));
+    fields.push(("tree", node(&scan.root, scan, tree_counts, cli, 0)));
+
+    let mut out = Value::object(fields).to_json(pretty);
+    out.push('\n');
+    out
+}
+
+fn json_count_map(map: Option<&BTreeMap<String, usize>>) -> Value {
+    Value::object(
+        map.into_iter()
+            .flatten()
+            .map(|(k, v)| (k.as_str(), Value::from(*v))),
+    )
+}
+
+fn json_lines(lines: LineCounts) -> Value {
+    Value::object([
+        ("code", Value::from(lines.code)),
+        ("comment", lines.comment.into()),
+        ("blank", lines.blank.into()),
+    ])
+}
+
+fn sorted(set: &HashSet<String>) -> Vec<String> {
+    let mut v = set.iter().cloned().collect::<Vec<_>>();
+    v.sort();
+    v
 }
 
-fn escape_json(s: &str) -> String {
-    s.replace('\\', "\\\\").replace('"', "\\\"")
+pub fn render_language_list(
+    languages: &[LanguageInfo],
+    selection: &Selection,
+    langs: &[String],
+    format: Format,
+    pretty: bool,
+) -> String {
+    let mut out = String::new();
+    if format == Format::Json {
+        let entries = languages
+            .iter()
+            .map(|l| {
+                Value::object([
+                    ("name", Value::from(l.name.as_str())),
+                    ("display_name", l.display_name.as_str().into()),
+                    ("type", l.kind.clone().into()),
+                    ("group", l.group.clone().into()),
+                    ("aliases", l.aliases.clone().into()),
+                    ("extensions", l.extensions.clone().into()),
+                    ("filenames", l.filenames.clone().into()),
+                    ("interpreters", l.interpreters.clone().into()),
+                ])
+            })
+            .collect();
+        let selection = Value::object([
+            ("lang", Value::from(display_langs(langs))),
+            ("extensions", sorted(&selection.extensions).into()),
+            (
+                "excluded_extensions",
+                sorted(&selection.excluded_extensions).into(),
+            ),
+            ("filenames", sorted(&selection.filenames).into()),
+            ("interpreters", sorted(&selection.interpreters).into()),
+        ]);
+        out.push_str(
+            &Value::object([
+                ("languages", Value::Array(entries)),
+                ("selection", selection),
+            ])
+            .to_json(pretty),
+        );
+        out.push('\n');
+        return out;
+    }
+
+    out.push_str(&format!("Known languages ({}):\n", languages.len()));
+    for l in languages {
+        let mut header = format!("{} ({})", l.name, l.display_name);
+        if let Some(kind) = &l.kind {
+            header.push_str(&format!(" [{kind}]"));
+        }
+        if let Some(group) = &l.group {
+            header.push_str(&format!(" group: {group}"));
+        }
+        out.push_str(&header);
+        out.push('\n');
+        for (label, values) in [
+            ("aliases", &l.aliases),
+            ("extensions", &l.extensions),
+            ("filenames", &l.filenames),
+            ("interpreters", &l.interpreters),
+        ] {
+            if !values.is_empty() {
+                out.push_str(&format!("  {label}: {}\n", values.join(",")));
+            }
+        }
+    }
+
+    out.push_str("============================================================\n");
+    out.push_str(&format!("Selection for --lang {}:\n", display_langs(langs)));
+    for (label, values) in [
+        ("Extensions", &selection.extensions),
+        ("Excluded extensions", &selection.excluded_extensions),
+        ("Filenames", &selection.filenames),
+        ("Interpreters", &selection.interpreters),
+    ] {
+        let values = sorted(values);
+        out.push_str(&format!(
+            "{label}: {}\n",
+            if values.is_empty() {
+                "(none)".to_string()
+            } else {
+                values.join(",")
+            }
+        ));
+    }
+    out
 }
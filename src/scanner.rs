@@ -1,188 +1,837 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
 use std::ffi::OsStr;
 use std::fs;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
 
 use crate::cli::Cli;
-use crate::model::{DirData, ScanResult};
+use crate::lang::{CommentSyntax, LanguageRegistry, Selection, interpreter_from_shebang};
+use crate::lines::count_lines;
+use crate::model::{Counts, DirData, LineCounts, ScanResult, ScanWarning};
 
-pub fn load_gitignore_patterns(root: &Path) -> Vec<String> {
-    let mut out = vec![];
-    let path = root.join(".gitignore");
-    let Ok(content) = fs::read_to_string(path) else {
-        return out;
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+/// Rules from a single gitignore-style file, scoped to the directory it lives in.
+#[derive(Debug, Clone, Default)]
+pub struct Gitignore {
+    base: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+impl Gitignore {
+    /// Parses `content` as a gitignore file located at `base`, relative to the scan root.
+    pub fn parse(base: &Path, content: &str) -> Self {
+        let rules = content.lines().filter_map(parse_ignore_line).collect();
+        Self {
+            base: base.to_path_buf(),
+            rules,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns `Some(true)` if the last matching rule ignores `rel`, `Some(false)` if it
+    /// re-includes it with `!`, and `None` if no rule applies.
+    pub fn matched(&self, rel: &Path, is_dir: bool) -> Option<bool> {
+        let local = rel.strip_prefix(&self.base).ok()?;
+        let local = path_to_slash(local);
+        if local.is_empty() {
+            return None;
+        }
+        let basename = local.rsplit('/').next().unwrap_or(&local);
+
+        self.rules.iter().rev().find_map(|rule| {
+            if rule.dir_only && !is_dir {
+                return None;
+            }
+            let text = if rule.anchored { &local[..] } else { basename };
+            wildmatch(rule.pattern.as_bytes(), text.as_bytes()).then_some(!rule.negated)
+        })
+    }
+}
+
+fn parse_ignore_line(line: &str) -> Option<IgnoreRule> {
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    // Trailing spaces are dropped unless escaped with a backslash.
+    let mut line = line.trim_end_matches(['\r', '\n']);
+    while line.ends_with(' ') && !line.ends_with("\\ ") {
+        line = &line[..line.len() - 1];
+    }
+    if line.is_empty() {
+        return None;
+    }
+
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    if line.is_empty() {
+        return None;
+    }
+
+    let anchored = line.contains('/');
+    let pattern = line.strip_prefix('/').unwrap_or(line).to_string();
+    if pattern.is_empty() {
+        return None;
+    }
+
+    Some(IgnoreRule {
+        pattern,
+        negated,
+        dir_only,
+        anchored,
+    })
+}
+
+/// Returns true if `rel` is ignored by `stack`, where later entries take precedence.
+pub fn is_ignored(stack: &[Gitignore], rel: &Path, is_dir: bool) -> bool {
+    stack
+        .iter()
+        .rev()
+        .find_map(|g| g.matched(rel, is_dir))
+        .unwrap_or(false)
+}
+
+fn path_to_slash(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Matches `text` against a gitignore glob, where `*`, `?` and classes stop at `/` and
+/// `**` spans directories when it forms a whole path segment.
+fn wildmatch(pat: &[u8], text: &[u8]) -> bool {
+    let (mut pi, mut ti) = (0, 0);
+    while pi < pat.len() {
+        match pat[pi] {
+            b'\\' if pi + 1 < pat.len() => {
+                pi += 1;
+                if text.get(ti) != Some(&pat[pi]) {
+                    return false;
+                }
+            }
+            b'?' => {
+                if ti >= text.len() || text[ti] == b'/' {
+                    return false;
+                }
+            }
+            b'*' => {
+                let start = pi;
+                while pi < pat.len() && pat[pi] == b'*' {
+                    pi += 1;
+                }
+                let whole_segment = pi - start >= 2
+                    && (start == 0 || pat[start - 1] == b'/')
+                    && (pi == pat.len() || pat[pi] == b'/');
+                if whole_segment {
+                    if pi == pat.len() {
+                        return true;
+                    }
+                    // `**/` matches zero or more leading directories.
+                    let rest = &pat[pi + 1..];
+                    if wildmatch(rest, &text[ti..]) {
+                        return true;
+                    }
+                    return (ti..text.len())
+                        .any(|k| text[k] == b'/' && wildmatch(rest, &text[k + 1..]));
+                }
+
+                let rest = &pat[pi..];
+                let mut k = ti;
+                loop {
+                    if wildmatch(rest, &text[k..]) {
+                        return true;
+                    }
+                    if k >= text.len() || text[k] == b'/' {
+                        return false;
+                    }
+                    k += 1;
+                }
+            }
+            b'[' => {
+                if ti >= text.len() || text[ti] == b'/' {
+                    return false;
+                }
+                match match_class(&pat[pi..], text[ti]) {
+                    Some((true, len)) => pi += len - 1,
+                    _ => return false,
+                }
+            }
+            c => {
+                if text.get(ti) != Some(&c) {
+                    return false;
+                }
+            }
+        }
+        pi += 1;
+        ti += 1;
+    }
+    ti == text.len()
+}
+
+/// Matches `c` against the bracket expression at the start of `pat`. Returns whether it
+/// matched and the length of the expression, or `None` if the bracket is unterminated.
+fn match_class(pat: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pat.get(i), Some(b'!' | b'^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let &b = pat.get(i)?;
+        if b == b']' && !first {
+            break;
+        }
+        first = false;
+
+        if b == b'[' && pat.get(i + 1) == Some(&b':') {
+            let end = pat[i + 2..].windows(2).position(|w| w == b":]")? + i + 2;
+            let class = &pat[i + 2..end];
+            matched |= match class {
+                b"alnum" => c.is_ascii_alphanumeric(),
+                b"alpha" => c.is_ascii_alphabetic(),
+                b"blank" => c == b' ' || c == b'\t',
+                b"cntrl" => c.is_ascii_control(),
+                b"digit" => c.is_ascii_digit(),
+                b"graph" => c.is_ascii_graphic(),
+                b"lower" => c.is_ascii_lowercase(),
+                b"print" => c.is_ascii_graphic() || c == b' ',
+                b"punct" => c.is_ascii_punctuation(),
+                b"space" => c.is_ascii_whitespace(),
+                b"upper" => c.is_ascii_uppercase(),
+                b"xdigit" => c.is_ascii_hexdigit(),
+                _ => return None,
+            };
+            i = end + 2;
+            continue;
+        }
+
+        let lo = if b == b'\\' {
+            i += 1;
+            *pat.get(i)?
+        } else {
+            b
+        };
+        if pat.get(i + 1) == Some(&b'-') && pat.get(i + 2).is_some_and(|&n| n != b']') {
+            i += 2;
+            let hi = if pat[i] == b'\\' {
+                i += 1;
+                *pat.get(i)?
+            } else {
+                pat[i]
+            };
+            matched |= lo <= c && c <= hi;
+        } else {
+            matched |= lo == c;
+        }
+        i += 1;
+    }
+
+    Some((matched != negated, i + 1))
+}
+
+pub fn load_gitignore_patterns(root: &Path) -> Vec<Gitignore> {
+    read_ignore_file(&root.join(".gitignore"), Path::new(""))
+        .into_iter()
+        .collect()
+}
+
+/// Loads `.git/info/exclude` for the repository at `root`, if there is one.
+pub fn load_info_exclude(root: &Path) -> Vec<Gitignore> {
+    git_dir(root)
+        .and_then(|dir| read_ignore_file(&dir.join("info").join("exclude"), Path::new("")))
+        .into_iter()
+        .collect()
+}
+
+/// Loads the user's global excludes file: `core.excludesFile` if it is configured, otherwise
+/// `$XDG_CONFIG_HOME/git/ignore` (or `~/.config/git/ignore`).
+pub fn load_global_excludes(root: &Path) -> Vec<Gitignore> {
+    let home = env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from);
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|h| h.join(".config")));
+
+    // Same order git reads them in, so later files override earlier ones.
+    let mut configs = vec![];
+    configs.extend(xdg.as_ref().map(|x| x.join("git").join("config")));
+    configs.extend(home.as_ref().map(|h| h.join(".gitconfig")));
+    configs.extend(git_dir(root).map(|d| d.join("config")));
+
+    let configured = configs
+        .iter()
+        .filter_map(|c| fs::read_to_string(c).ok())
+        .filter_map(|content| config_excludes_file(&content))
+        .next_back();
+    let path = match configured {
+        Some(v) => match (v.strip_prefix("~/"), &home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(v),
+        },
+        None => match xdg {
+            Some(x) => x.join("git").join("ignore"),
+            None => return vec![],
+        },
     };
+
+    read_ignore_file(&path, Path::new("")).into_iter().collect()
+}
+
+/// Resolves the git directory for `root`, following `gitdir:` files used by worktrees
+/// and submodules.
+fn git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = fs::read_to_string(&dot_git).ok()?;
+    let target = content.trim().strip_prefix("gitdir:")?.trim();
+    Some(root.join(target))
+}
+
+/// Extracts `core.excludesFile` from a git config file, keeping the last assignment.
+fn config_excludes_file(content: &str) -> Option<String> {
+    let mut in_core = false;
+    let mut found = None;
     for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() || trimmed.starts_with('#') {
+        let line = line.trim();
+        if line.starts_with('[') {
+            let section = line.trim_start_matches('[').split([']', ' ', '"']).next();
+            in_core = section.is_some_and(|s| s.eq_ignore_ascii_case("core"));
+            continue;
+        }
+        if !in_core {
             continue;
         }
-        out.push(trimmed.to_string());
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if !key.trim().eq_ignore_ascii_case("excludesfile") {
+            continue;
+        }
+        let value = value
+            .split([';', '#'])
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .trim_matches('"');
+        if !value.is_empty() {
+            found = Some(value.to_string());
+        }
+    }
+    found
+}
+
+fn read_ignore_file(file: &Path, base: &Path) -> Option<Gitignore> {
+    let content = fs::read_to_string(file).ok()?;
+    let ignore = Gitignore::parse(base, &content);
+    (!ignore.is_empty()).then_some(ignore)
+}
+
+pub const DEFAULT_SKIP_DIRS: &[&str] = &[
+    ".git",
+    "target",
+    "build",
+    "out",
+    "node_modules",
+    "third_party",
+    "dist",
+];
+
+/// Directory names skipped at any depth: the defaults (unless `--no-default-excludes`),
+/// plus `--skip`, minus `--no-skip`.
+pub fn effective_skip_dirs(cli: &Cli) -> Vec<String> {
+    let defaults = if cli.no_default_excludes {
+        &[][..]
+    } else {
+        DEFAULT_SKIP_DIRS
+    };
+
+    let mut out: Vec<String> = vec![];
+    for name in defaults
+        .iter()
+        .copied()
+        .chain(cli.skip.iter().map(String::as_str))
+    {
+        if !cli.no_skip.iter().any(|n| n == name) && !out.iter().any(|n| n == name) {
+            out.push(name.to_string());
+        }
     }
     out
 }
 
 pub fn scan_tree(
     root: &Path,
-    extensions: &HashSet<String>,
+    registry: &LanguageRegistry,
+    selection: &Selection,
     cli: &Cli,
-    gitignore: &[String],
+    gitignore: &[Gitignore],
 ) -> Result<ScanResult, String> {
-    let default_skip = [
-        ".git",
-        "target",
-        "build",
-        "out",
-        "node_modules",
-        "third_party",
-        "dist",
-    ];
+    let skip_dirs = effective_skip_dirs(cli);
+
+    let threads = cli
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .max(1);
+    let walker = Walker {
+        root,
+        registry,
+        selection,
+        cli,
+        skip_dirs: &skip_dirs,
+        queues: (0..threads).map(|_| Mutex::default()).collect(),
+        pending: Mutex::new(0),
+        wake: Condvar::new(),
+        failed: AtomicBool::new(false),
+        error: Mutex::new(None),
+        claims: Mutex::default(),
+    };
+    walker.push(
+        0,
+        Job {
+            dir: root.to_path_buf(),
+            depth: 0,
+            ignores: Arc::new(gitignore.to_vec()),
+        },
+    );
+
+    let outputs = thread::scope(|scope| {
+        let handles = (1..threads)
+            .map(|worker| {
+                let walker = &walker;
+                scope.spawn(move || walker.run(worker))
+            })
+            .collect::<Vec<_>>();
+        let mut outputs = vec![walker.run(0)];
+        outputs.extend(
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("walker thread panicked")),
+        );
+        outputs
+    });
+
+    if let Some(err) = walker.error.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        return Err(err);
+    }
 
     let mut dirs = HashMap::new();
     dirs.insert(
         root.to_path_buf(),
         DirData {
-            name: root
-                .file_name()
-                .unwrap_or_else(|| OsStr::new("."))
-                .to_string_lossy()
-                .to_string(),
+            name: dir_name(root),
             ..DirData::default()
         },
     );
+    let mut walked = vec![];
+    let mut warnings = vec![];
+    for out in outputs {
+        for (path, data) in out.dirs {
+            let entry = dirs.entry(path).or_insert_with(DirData::default);
+            if entry.name.is_empty() {
+                entry.name = data.name;
+            }
+            entry.children.extend(data.children);
+            entry.direct_files += data.direct_files;
+            entry.bytes += data.bytes;
+            entry.lines.add(data.lines);
+            for (lang, n) in data.languages {
+                *entry.languages.entry(lang).or_default() += n;
+            }
+            entry.partial |= data.partial;
+        }
+        walked.extend(out.walked);
+        warnings.extend(out.warnings);
+    }
+
+    if cli.follow_symlinks {
+        // A directory reachable through several links is only counted under the path that
+        // won its claim, so the result does not depend on thread scheduling.
+        let claims = walker
+            .claims
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner());
+        let mut losers = HashSet::new();
+        for (dir, canon) in walked {
+            if claims.get(&canon) != Some(&dir) {
+                if let Some(data) = dirs.get_mut(&dir) {
+                    data.children.clear();
+                    data.direct_files = 0;
+                    data.bytes = 0;
+                    data.languages.clear();
+                    data.lines = LineCounts::default();
+                    data.partial = false;
+                }
+                losers.insert(dir);
+            }
+        }
+
+        let mut reachable = HashSet::new();
+        let mut stack = vec![root.to_path_buf()];
+        while let Some(path) = stack.pop() {
+            if let Some(data) = dirs.get(&path) {
+                stack.extend(data.children.iter().cloned());
+            }
+            reachable.insert(path);
+        }
+        dirs.retain(|path, _| reachable.contains(path));
+        warnings.retain(|(dir, _)| reachable.contains(dir) && !losers.contains(dir));
+    }
+
+    let mut warnings = warnings.into_iter().map(|(_, w)| w).collect::<Vec<_>>();
+    warnings.sort_by(|a, b| a.path.cmp(&b.path));
+
+    // A pruned directory leaves every ancestor with an incomplete subtree total as well.
+    let pruned = dirs
+        .iter()
+        .filter(|(_, d)| d.partial)
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    for path in pruned {
+        for ancestor in path.ancestors().skip(1) {
+            match dirs.get_mut(ancestor) {
+                Some(data) if !data.partial => data.partial = true,
+                _ => break,
+            }
+            if ancestor == root {
+                break;
+            }
+        }
+    }
+
+    let total_files = dirs.values().map(|d| d.direct_files).sum();
+    let total_bytes = dirs.values().map(|d| d.bytes).sum();
+    let dirs_with_files = dirs
+        .iter()
+        .filter(|(path, d)| d.direct_files > 0 && path.as_path() != root)
+        .count();
+
+    Ok(ScanResult {
+        root: root.to_path_buf(),
+        dirs,
+        total_files,
+        total_bytes,
+        dirs_with_files,
+        warnings,
+        skip_dirs,
+    })
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_else(|| OsStr::new("."))
+        .to_string_lossy()
+        .to_string()
+}
+
+struct Job {
+    dir: PathBuf,
+    depth: usize,
+    ignores: Arc<Vec<Gitignore>>,
+}
 
-    let mut total_files = 0usize;
-    let mut dirs_with_files = HashSet::new();
-    let mut visited = HashSet::new();
+#[derive(Default)]
+struct WorkerOutput {
+    dirs: HashMap<PathBuf, DirData>,
+    walked: Vec<(PathBuf, PathBuf)>,
+    warnings: Vec<(PathBuf, ScanWarning)>,
+}
+
+/// Work-stealing directory walker. Each worker pops from the back of its own queue, which
+/// keeps the walk depth-first, and steals from the front of the others when it runs dry.
+/// Idle workers sleep on `wake` until a job is queued or the walk is finished.
+struct Walker<'a> {
+    root: &'a Path,
+    registry: &'a LanguageRegistry,
+    selection: &'a Selection,
+    cli: &'a Cli,
+    skip_dirs: &'a [String],
+    queues: Vec<Mutex<VecDeque<Job>>>,
+    /// Jobs queued or in progress; the walk is done when this drops to zero.
+    pending: Mutex<usize>,
+    wake: Condvar,
+    failed: AtomicBool,
+    error: Mutex<Option<String>>,
+    claims: Mutex<HashMap<PathBuf, PathBuf>>,
+}
+
+impl<'a> Walker<'a> {
+    fn push(&self, worker: usize, job: Job) {
+        let mut pending = lock(&self.pending);
+        *pending += 1;
+        lock(&self.queues[worker]).push_back(job);
+        self.wake.notify_one();
+    }
+
+    fn next_job(&self, worker: usize) -> Option<Job> {
+        if let Some(job) = lock(&self.queues[worker]).pop_back() {
+            return Some(job);
+        }
+        let n = self.queues.len();
+        (1..n).find_map(|offset| lock(&self.queues[(worker + offset) % n]).pop_front())
+    }
+
+    fn run(&self, worker: usize) -> WorkerOutput {
+        let mut out = WorkerOutput::default();
+        loop {
+            if let Some(job) = self.next_job(worker) {
+                if !self.failed.load(Ordering::Relaxed) {
+                    self.visit(worker, job, &mut out);
+                }
+                let mut pending = lock(&self.pending);
+                *pending -= 1;
+                if *pending == 0 {
+                    self.wake.notify_all();
+                }
+                continue;
+            }
+
+            // `push` holds `pending` while queueing, so no job can slip in between this
+            // check and the wait.
+            let mut pending = lock(&self.pending);
+            while *pending > 0 && self.queues.iter().all(|q| lock(q).is_empty()) {
+                pending = self.wake.wait(pending).unwrap_or_else(|e| e.into_inner());
+            }
+            if *pending == 0 {
+                return out;
+            }
+        }
+    }
+
+    /// Stops the walk, keeping the first error for `scan_tree` to return.
+    fn abort(&self, path: &Path, err: io::Error) {
+        self.failed.store(true, Ordering::Relaxed);
+        lock(&self.error).get_or_insert(format!("failed to read {}: {err}", path.display()));
+    }
+
+    /// Records a path that could not be read.
+    fn warn(&self, out: &mut WorkerOutput, dir: &Path, path: &Path, err: io::Error) {
+        out.warnings.push((
+            dir.to_path_buf(),
+            ScanWarning {
+                path: path.to_path_buf(),
+                reason: err.to_string(),
+            },
+        ));
+    }
 
-    struct WalkCtx<'a> {
-        root: &'a Path,
-        extensions: &'a HashSet<String>,
-        cli: &'a Cli,
-        gitignore: &'a [String],
-        default_skip: &'a [&'a str],
-        dirs: &'a mut HashMap<PathBuf, DirData>,
-        total_files: &'a mut usize,
-        dirs_with_files: &'a mut HashSet<PathBuf>,
-        visited: &'a mut HashSet<PathBuf>,
+    /// Records `dir` as the owner of its canonical path unless a shallower (or equally deep
+    /// but smaller) path already is.
+    fn claim(&self, dir: &Path, canon: PathBuf) -> bool {
+        let key = |p: &Path| (p.components().count(), p.to_path_buf());
+        let mut claims = lock(&self.claims);
+        match claims.get_mut(&canon) {
+            Some(owner) if key(owner) <= key(dir) => false,
+            Some(owner) => {
+                *owner = dir.to_path_buf();
+                true
+            }
+            None => {
+                claims.insert(canon, dir.to_path_buf());
+                true
+            }
+        }
     }
 
-    fn walk(current: &Path, depth: usize, ctx: &mut WalkCtx<'_>) -> Result<(), String> {
-        let canon = current
-            .canonicalize()
-            .unwrap_or_else(|_| current.to_path_buf());
-        if ctx.cli.follow_symlinks && !ctx.visited.insert(canon) {
-            return Ok(());
+    fn visit(&self, worker: usize, job: Job, out: &mut WorkerOutput) {
+        let current = job.dir.as_path();
+        if self.cli.follow_symlinks {
+            let canon = current
+                .canonicalize()
+                .unwrap_or_else(|_| current.to_path_buf());
+            if !self.claim(current, canon.clone()) {
+                return;
+            }
+            out.walked.push((current.to_path_buf(), canon));
+        }
+
+        // Only a directory that cannot be listed at all is fatal under `--strict` (or at the
+        // root); entries that fail later are always just warnings.
+        let entries = match fs::read_dir(current) {
+            Ok(v) => v,
+            Err(e) if self.cli.strict || current == self.root => {
+                self.abort(current, e);
+                return;
+            }
+            Err(e) => {
+                self.warn(out, current, current, e);
+                return;
+            }
+        };
+
+        // Nested .gitignore files apply to everything below the directory holding them.
+        let mut ignores = job.ignores;
+        if !self.cli.no_gitignore && current != self.root {
+            let rel = current.strip_prefix(self.root).unwrap_or(current);
+            if let Some(nested) = read_ignore_file(&current.join(".gitignore"), rel) {
+                let mut stack = ignores.as_ref().clone();
+                stack.push(nested);
+                ignores = Arc::new(stack);
+            }
         }
 
-        let entries = fs::read_dir(current)
-            .map_err(|e| format!("failed to read {}: {e}", current.display()))?;
         for entry in entries {
             let entry = match entry {
                 Ok(v) => v,
-                Err(_) => continue,
+                Err(e) => {
+                    self.warn(out, current, current, e);
+                    continue;
+                }
             };
             let path = entry.path();
-            let rel = path.strip_prefix(ctx.root).unwrap_or(&path);
-            if should_exclude(rel, ctx.cli, ctx.gitignore, ctx.default_skip) {
-                continue;
-            }
+            let rel = path.strip_prefix(self.root).unwrap_or(&path);
 
-            let metadata = if ctx.cli.follow_symlinks {
+            let metadata = if self.cli.follow_symlinks {
                 fs::metadata(&path)
             } else {
                 fs::symlink_metadata(&path)
             };
-            let Ok(meta) = metadata else { continue };
+            let meta = match metadata {
+                Ok(v) => v,
+                Err(e) => {
+                    if !self.should_exclude(rel, false, &ignores) {
+                        self.warn(out, current, &path, e);
+                    }
+                    continue;
+                }
+            };
             let ft = meta.file_type();
 
-            if ft.is_symlink() && !ctx.cli.follow_symlinks {
+            if ft.is_symlink() && !self.cli.follow_symlinks {
+                continue;
+            }
+
+            if self.should_exclude(rel, ft.is_dir(), &ignores) {
                 continue;
             }
 
             if ft.is_dir() {
-                ctx.dirs.entry(path.clone()).or_insert_with(|| DirData {
-                    name: path
-                        .file_name()
-                        .unwrap_or_else(|| OsStr::new("."))
-                        .to_string_lossy()
-                        .to_string(),
+                if self.cli.prune && self.cli.max_depth.is_some_and(|max| job.depth >= max) {
+                    out.dirs.entry(current.to_path_buf()).or_default().partial = true;
+                    continue;
+                }
+                out.dirs.entry(path.clone()).or_insert_with(|| DirData {
+                    name: dir_name(&path),
                     ..DirData::default()
                 });
-                if let Some(parent) = path.parent() {
-                    ctx.dirs
-                        .entry(parent.to_path_buf())
-                        .or_default()
-                        .children
-                        .insert(path.clone());
-                }
-
-                walk(&path, depth + 1, ctx)?;
+                out.dirs
+                    .entry(current.to_path_buf())
+                    .or_default()
+                    .children
+                    .insert(path.clone());
+                self.push(
+                    worker,
+                    Job {
+                        dir: path,
+                        depth: job.depth + 1,
+                        ignores: Arc::clone(&ignores),
+                    },
+                );
                 continue;
             }
 
-            if ft.is_file() {
-                let ext = path
-                    .extension()
-                    .and_then(|v| v.to_str())
-                    .map(|v| v.to_ascii_lowercase());
-                if let Some(ext) = ext
-                    && ctx.extensions.contains(&ext)
-                {
-                    *ctx.total_files += 1;
-                    let parent = path.parent().unwrap_or(ctx.root).to_path_buf();
-                    ctx.dirs.entry(parent.clone()).or_default().direct_files += 1;
-                    if parent != ctx.root {
-                        ctx.dirs_with_files.insert(parent);
+            if !ft.is_file() {
+                continue;
+            }
+            if let Some(lang) = self.match_language(&path) {
+                let lines = if self.cli.lines {
+                    match fs::read(&path) {
+                        Ok(bytes) => count_file_lines(&bytes, self.registry.comment_syntax(lang)),
+                        Err(e) => {
+                            self.warn(out, current, &path, e);
+                            LineCounts::default()
+                        }
+                    }
+                } else {
+                    LineCounts::default()
+                };
+                let data = out.dirs.entry(current.to_path_buf()).or_default();
+                data.direct_files += 1;
+                data.bytes += meta.len();
+                data.lines.add(lines);
+                match data.languages.get_mut(lang) {
+                    Some(n) => *n += 1,
+                    None => {
+                        data.languages.insert(lang.to_string(), 1);
                     }
                 }
             }
         }
-        Ok(())
     }
 
-    let mut ctx = WalkCtx {
-        root,
-        extensions,
-        cli,
-        gitignore,
-        default_skip: &default_skip,
-        dirs: &mut dirs,
-        total_files: &mut total_files,
-        dirs_with_files: &mut dirs_with_files,
-        visited: &mut visited,
-    };
-    walk(root, 0, &mut ctx)?;
+    /// Returns the language a selected file is counted as, or `None` if it is not selected.
+    fn match_language(&self, path: &Path) -> Option<&'a str> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(lang) = self.selection.filename_languages.get(name.as_ref()) {
+            return Some(lang);
+        }
+        if let Some((suffix, selected)) = self.selection.match_extension(&name) {
+            return selected.then(|| self.selection.extension_language(&suffix));
+        }
 
-    Ok(ScanResult {
-        root: root.to_path_buf(),
-        dirs,
-        total_files,
-        dirs_with_files: dirs_with_files.len(),
-    })
-}
+        // Only extensionless files are sniffed, so the common case costs no extra reads.
+        if path.extension().is_some() || self.selection.interpreters.is_empty() {
+            return None;
+        }
+        let interpreter = read_shebang(path).and_then(|line| interpreter_from_shebang(&line))?;
+        self.selection
+            .interpreter_languages
+            .get(&interpreter)
+            .map(String::as_str)
+    }
 
-fn should_exclude(rel: &Path, cli: &Cli, gitignore: &[String], default_skip: &[&str]) -> bool {
-    let rel_str = rel.to_string_lossy();
-    let comps = rel
-        .components()
-        .map(|c| c.as_os_str().to_string_lossy())
-        .collect::<Vec<_>>();
+    fn should_exclude(&self, rel: &Path, is_dir: bool, ignores: &[Gitignore]) -> bool {
+        let comps = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
 
-    if !cli.hidden && comps.iter().any(|c| c.starts_with('.')) {
-        return true;
-    }
-    if comps.iter().any(|c| default_skip.contains(&c.as_ref())) {
-        return true;
-    }
-    if cli.exclude.iter().any(|pat| simple_match(&rel_str, pat)) {
-        return true;
-    }
-    if gitignore.iter().any(|pat| simple_match(&rel_str, pat)) {
-        return true;
+        if !self.cli.hidden && comps.iter().any(|c| c.starts_with('.')) {
+            return true;
+        }
+        if comps
+            .iter()
+            .any(|c| self.skip_dirs.iter().any(|s| *s == *c))
+        {
+            return true;
+        }
+        let rel_str = rel.to_string_lossy();
+        if self
+            .cli
+            .exclude
+            .iter()
+            .any(|pat| simple_match(&rel_str, pat))
+        {
+            return true;
+        }
+        is_ignored(ignores, rel, is_dir)
     }
-
-    false
 }
 
 fn simple_match(path: &str, pattern: &str) -> bool {
@@ -198,25 +847,63 @@ fn simple_match(path: &str, pattern: &str) -> bool {
     path.contains(pattern.trim_matches('/'))
 }
 
+fn read_shebang(path: &Path) -> Option<String> {
+    let mut buf = [0u8; 256];
+    let mut file = fs::File::open(path).ok()?;
+    let n = file.read(&mut buf).ok()?;
+    let head = &buf[..n];
+    if !head.starts_with(b"#!") {
+        return None;
+    }
+    let end = head.iter().position(|&b| b == b'\n').unwrap_or(n);
+    Some(String::from_utf8_lossy(&head[..end]).into_owned())
+}
+
+/// Binary files are counted as files but contribute no lines.
+fn count_file_lines(bytes: &[u8], syntax: Option<&CommentSyntax>) -> LineCounts {
+    if bytes[..bytes.len().min(8192)].contains(&0) {
+        return LineCounts::default();
+    }
+    let text = String::from_utf8_lossy(bytes);
+    match syntax {
+        Some(syntax) => count_lines(&text, syntax),
+        None => count_lines(&text, &CommentSyntax::default()),
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
 pub fn compute_tree_counts(
     root: &Path,
     dirs: &HashMap<PathBuf, DirData>,
-) -> HashMap<PathBuf, usize> {
+) -> HashMap<PathBuf, Counts> {
     fn dfs(
         path: &Path,
         dirs: &HashMap<PathBuf, DirData>,
-        memo: &mut HashMap<PathBuf, usize>,
-    ) -> usize {
+        memo: &mut HashMap<PathBuf, Counts>,
+    ) -> Counts {
         if let Some(v) = memo.get(path) {
-            return *v;
+            return v.clone();
         }
-        let mut sum = dirs.get(path).map_or(0, |d| d.direct_files);
+        let mut sum = Counts::default();
         if let Some(dir) = dirs.get(path) {
+            sum.files = dir.direct_files;
+            sum.bytes = dir.bytes;
+            sum.languages = dir.languages.clone();
+            sum.lines = dir.lines;
             for child in &dir.children {
-                sum += dfs(child, dirs, memo);
+                let counts = dfs(child, dirs, memo);
+                sum.files += counts.files;
+                sum.bytes += counts.bytes;
+                sum.lines.add(counts.lines);
+                for (lang, n) in counts.languages {
+                    *sum.languages.entry(lang).or_default() += n;
+                }
             }
         }
-        memo.insert(path.to_path_buf(), sum);
+        memo.insert(path.to_path_buf(), sum.clone());
         sum
     }
 
@@ -3,6 +3,8 @@ use std::path::Path;
 use std::process::Command;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde_json::{Value, json};
+
 fn bin_path() -> String {
     env!("CARGO_BIN_EXE_treestat").to_string()
 }
@@ -24,6 +26,28 @@ fn write(path: &Path, content: &str) {
     fs::write(path, content).unwrap();
 }
 
+/// A treestat command that scans `root` with `args`.
+fn treestat(root: &Path, args: &[&str]) -> Command {
+    let mut cmd = Command::new(bin_path());
+    cmd.arg(root).args(args);
+    cmd
+}
+
+/// Runs `cmd` and returns its stdout, failing the test if treestat did not succeed.
+fn stdout(cmd: &mut Command) -> String {
+    let out = cmd.output().unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8_lossy(&out.stdout).to_string()
+}
+
+fn run(root: &Path, args: &[&str]) -> String {
+    stdout(&mut treestat(root, args))
+}
+
 #[test]
 fn text_tree_output_for_rust() {
     let root = make_temp_dir();
@@ -144,3 +168,1013 @@ fn multi_language_count_includes_c_cpp_and_headers() {
 
     let _ = fs::remove_dir_all(root);
 }
+
+#[test]
+fn headers_only_counts_just_header_files() {
+    let root = make_temp_dir();
+    write(&root.join("inc/a.h"), "#pragma once\n");
+    write(&root.join("m.c"), "int main(void) { return 0; }\n");
+    write(&root.join("Makefile"), "all:\n");
+    write(&root.join("run"), "#!/bin/sh\necho hi\n");
+
+    let s = run(&root, &["--headers", "only"]);
+    assert!(s.contains("Total matching files: 1"));
+    assert!(s.contains("inc/ (1)"));
+    assert!(!s.contains("makefile") && !s.contains("shell"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn nested_gitignore_files_are_honored() {
+    let root = make_temp_dir();
+    write(&root.join(".gitignore"), "generated/\n*.pb.rs\n");
+    write(&root.join("src/main.rs"), "fn main(){}\n");
+    write(&root.join("src/api.pb.rs"), "\n");
+    write(&root.join("src/generated/a.rs"), "\n");
+    write(
+        &root.join("crates/x/.gitignore"),
+        "/fixtures\n!keep.pb.rs\n",
+    );
+    write(&root.join("crates/x/fixtures/f.rs"), "\n");
+    write(&root.join("crates/x/keep.pb.rs"), "\n");
+    write(&root.join("crates/x/src/fixtures/f.rs"), "\n");
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .arg("--lang")
+        .arg("rust")
+        .output()
+        .unwrap();
+
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("Total matching files: 3"));
+    assert!(!s.contains("generated/"));
+    assert!(s.contains("x/ (2)"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn exclude_matches_anywhere_in_the_path() {
+    let root = make_temp_dir();
+    write(&root.join("src/gen/a.rs"), "\n");
+    write(&root.join("pkg/src/gen/b.rs"), "\n");
+    write(&root.join("pkg/src/lib.rs"), "\n");
+    write(&root.join("pkg/build.rs"), "\n");
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "rust",
+            "--exclude",
+            "src/gen",
+            "--exclude",
+            "*.build.rs",
+        ],
+    );
+    assert!(s.contains("Total matching files: 1"));
+    assert!(!s.contains("gen/"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn git_info_exclude_and_global_excludes_are_honored() {
+    let root = make_temp_dir();
+    let home = make_temp_dir();
+    write(&root.join(".git/info/exclude"), "scratch/\n");
+    write(
+        &home.join(".gitconfig"),
+        "[core]\n\texcludesFile = ~/global-ignore\n",
+    );
+    write(&home.join("global-ignore"), "*.local.py\n");
+    write(&root.join("app/main.py"), "\n");
+    write(&root.join("app/settings.local.py"), "\n");
+    write(&root.join("scratch/try.py"), "\n");
+
+    for (extra, expected) in [(None, 1), (Some("--no-git-excludes"), 3)] {
+        let mut cmd = treestat(&root, &["--lang", "python"]);
+        cmd.args(extra)
+            .env("HOME", &home)
+            .env_remove("XDG_CONFIG_HOME");
+        assert!(stdout(&mut cmd).contains(&format!("Total matching files: {expected}")));
+    }
+
+    let _ = fs::remove_dir_all(root);
+    let _ = fs::remove_dir_all(home);
+}
+
+#[test]
+fn parallel_walk_matches_serial_walk() {
+    let root = make_temp_dir();
+    for a in 0..6 {
+        for b in 0..5 {
+            for c in 0..4 {
+                write(
+                    &root.join(format!("d{a}/e{b}/f{c}/m{c}.rs")),
+                    "fn main(){}\n",
+                );
+            }
+            write(&root.join(format!("d{a}/e{b}/skip.rs")), "\n");
+        }
+        write(&root.join(format!("d{a}/.gitignore")), "skip.rs\n/e0\n");
+    }
+    write(&root.join("shared/lib.rs"), "\n");
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&root, root.join("d0/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("shared"), root.join("d1/link_a")).unwrap();
+        std::os::unix::fs::symlink(root.join("shared"), root.join("d2/link_b")).unwrap();
+    }
+
+    let scan = |threads: &str| {
+        let out = run(
+            &root,
+            &[
+                "--lang",
+                "rust",
+                "--format",
+                "json",
+                "--follow-symlinks",
+                "--threads",
+                threads,
+            ],
+        );
+        // Timing and the thread count are expected to differ; everything scanned must not.
+        let doc = serde_json::from_str::<Value>(&out).unwrap();
+        [
+            "total_files",
+            "total_bytes",
+            "dirs_with_files",
+            "warnings",
+            "tree",
+        ]
+        .map(|key| doc.get(key).cloned())
+    };
+
+    let serial = scan("1");
+    assert_eq!(serial[0], Some(json!(97)));
+    for _ in 0..5 {
+        assert_eq!(scan("8"), serial);
+    }
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[cfg(unix)]
+#[test]
+fn unreadable_paths_become_warnings_unless_strict() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = make_temp_dir();
+    write(&root.join("ok/a.rs"), "\n");
+    write(&root.join("locked/b.rs"), "\n");
+    std::os::unix::fs::symlink(root.join("missing"), root.join("ok/dangling")).unwrap();
+    fs::set_permissions(root.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+    // Privileged users can read the directory anyway, so only expect it when it fails.
+    let locked_fails = fs::read_dir(root.join("locked")).is_err();
+
+    let args = ["--lang", "rust", "--follow-symlinks"];
+
+    let s = run(&root, &args);
+    assert!(s.contains(&format!("Warnings ({}):", 1 + usize::from(locked_fails))));
+    assert!(s.contains("dangling: "));
+    assert_eq!(s.contains("locked: "), locked_fails);
+
+    let s = run(&root, &[&args[..], &["--format", "json"]].concat());
+    assert!(s.contains("\"warnings\":[{\"path\":"));
+
+    // `--strict` only aborts on a directory it cannot list; the dangling link stays a warning.
+    let out = treestat(&root, &args).arg("--strict").output().unwrap();
+    assert_eq!(out.status.success(), !locked_fails);
+    if locked_fails {
+        assert!(String::from_utf8_lossy(&out.stderr).contains("failed to read"));
+    } else {
+        let s = String::from_utf8_lossy(&out.stdout);
+        assert!(s.contains("Warnings (1):"));
+        assert!(s.contains("dangling: "));
+    }
+
+    fs::set_permissions(root.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn prune_stops_walking_at_max_depth() {
+    let root = make_temp_dir();
+    write(&root.join("src/main.rs"), "fn main(){}\n");
+    write(&root.join("src/nested/lib.rs"), "pub fn a(){}\n");
+    write(&root.join("deep/only/here.rs"), "\n");
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust", "--max-depth", "1", "--prune"])
+        .output()
+        .unwrap();
+
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("Total matching files: 1"));
+    assert!(s.contains("src/ (1+)"));
+    assert!(s.contains("deep/ (0+)"));
+    assert!(!s.contains("nested/"));
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust", "--prune"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn default_skip_list_is_configurable() {
+    let root = make_temp_dir();
+    write(&root.join("build/gen.go"), "package x\n");
+    write(&root.join("third_party/lib.go"), "package y\n");
+    write(&root.join("vendor/dep.go"), "package z\n");
+    write(&root.join("main.go"), "package main\n");
+
+    let s = run(&root, &["--lang", "go"]);
+    assert!(s.contains("Total matching files: 2"));
+    assert!(s.contains("Skipped directories: .git,target,build,out,node_modules,third_party,dist"));
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "go",
+            "--no-skip",
+            "build,third_party",
+            "--skip",
+            "vendor",
+        ],
+    );
+    assert!(s.contains("Total matching files: 3"));
+    assert!(s.contains("Skipped directories: .git,target,out,node_modules,dist,vendor"));
+
+    let s = run(
+        &root,
+        &["--lang", "go", "--no-default-excludes", "--format", "json"],
+    );
+    assert!(s.contains("\"total_files\":4"));
+    assert!(s.contains("\"skip_dirs\":[]"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn filenames_and_shebangs_are_detected() {
+    let root = make_temp_dir();
+    write(&root.join("Makefile"), "all:\n");
+    write(&root.join("docker/Dockerfile"), "FROM scratch\n");
+    write(&root.join("pkg/BUILD"), "cc_library()\n");
+    write(&root.join("CMakeLists.txt"), "project(x)\n");
+    write(&root.join("notes.txt"), "hello\n");
+    write(&root.join("bin/tool"), "#!/usr/bin/env python3\nprint(1)\n");
+    write(&root.join("bin/run"), "#!/bin/bash\necho hi\n");
+    write(&root.join("bin/data"), "plain text\n");
+
+    let s = run(&root, &["--lang", "all"]);
+    assert!(s.contains("Total matching files: 6"));
+    assert!(s.contains("bin/ (2)"));
+    let s = run(&root, &["--lang", "python"]);
+    assert!(s.contains("Total matching files: 1"));
+    let s = run(&root, &["--lang", "make,dockerfile,starlark,cmake"]);
+    assert!(s.contains("Total matching files: 4"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn compound_extensions_can_be_selected_and_excluded() {
+    let root = make_temp_dir();
+    write(&root.join("types/index.d.ts"), "export {};\n");
+    write(&root.join("src/app.ts"), "export {};\n");
+    write(&root.join("api/service.pb.go"), "package api\n");
+    write(&root.join("api/service.go"), "package api\n");
+
+    let s = run(&root, &["--lang", "go", "--ext", ".d.ts"]);
+    assert!(s.contains("Total matching files: 3"));
+    assert!(s.contains("types/ (1)"));
+    assert!(!s.contains("src/"));
+
+    let s = run(&root, &["--lang", "ts,go", "--exclude-ext", "pb.go,d.ts"]);
+    assert!(s.contains("Total matching files: 2"));
+    assert!(s.contains("Excluded extensions: d.ts,pb.go"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn list_languages_shows_registry_and_selection() {
+    let out = Command::new(bin_path())
+        .args(["--list-languages", "--lang", "ts", "--ext", "d.ts"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("kotlin (Kotlin) [programming]"));
+    assert!(s.contains("  aliases: bash,envrc,sh,shell-script,zsh"));
+    assert!(s.contains("Selection for --lang typescript:"));
+    assert!(s.contains("Extensions: cts,d.ts,mts,ts,tsx"));
+
+    let out = Command::new(bin_path())
+        .args(["--list-languages", "--lang", "python", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("{\"name\":\"python\",\"display_name\":\"Python\""));
+    assert!(s.contains("\"selection\":{\"lang\":\"python\",\"extensions\":[\"cgi\","));
+    assert!(s.contains("\"pytb\""));
+
+    for format in ["csv", "markdown", "flat"] {
+        let out = Command::new(bin_path())
+            .args(["--list-languages", "--format", format])
+            .output()
+            .unwrap();
+        assert!(!out.status.success());
+        assert!(
+            String::from_utf8_lossy(&out.stderr)
+                .contains("--list-languages only supports text and JSON")
+        );
+    }
+}
+
+#[test]
+fn user_languages_file_extends_registry() {
+    let root = make_temp_dir();
+    write(
+        &root.join("languages.yml"),
+        "---\nXDL:\n  type: programming\n  aliases:\n  - xdsl\n  extensions:\n  - \".xdl\"\nRust:\n  extensions: [\".rs.in\"]\n",
+    );
+    write(&root.join("src/schema/a.xdl"), "record A {}\n");
+    write(&root.join("src/schema/b.xdl"), "record B {}\n");
+    write(&root.join("src/lib.rs.in"), "fn main() {}\n");
+    write(&root.join("src/main.rs"), "fn main() {}\n");
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--languages-file"])
+        .arg(root.join("languages.yml"))
+        .args(["--lang", "xdsl"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("Total matching files: 2"));
+    assert!(s.contains("schema/ (2)"));
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--languages-file"])
+        .arg(root.join("languages.yml"))
+        .args(["--lang", "rust"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("Total matching files: 2"));
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "xdsl"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn per_language_counts_are_rolled_up() {
+    let root = make_temp_dir();
+    for i in 0..3 {
+        write(&root.join(format!("core/src/m{i}.rs")), "fn f() {}\n");
+    }
+    write(&root.join("core/build.py"), "print()\n");
+    write(&root.join("core/include/api.h"), "int f(void);\n");
+    write(&root.join("types/index.d.ts"), "export {};\n");
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust,python,c", "--ext", "d.ts"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("core/ (5)  [rust 3, c 1, python 1]"));
+    assert!(s.contains("src/ (3)  [rust 3]"));
+    assert!(s.contains("types/ (1)  [typescript 1]"));
+    assert!(s.contains("Languages: rust 3, c 1, python 1, typescript 1"));
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust,python,c", "--format", "json"])
+        .args(["--count-mode", "direct"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("\"languages\":{\"c\":1,\"python\":1,\"rust\":3}"));
+    assert!(s.contains("\"name\":\"core\",\"path\":"));
+    assert!(s.contains("\"files\":1,\"bytes\":8,\"languages\":{\"python\":1}"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn lines_mode_counts_code_comment_and_blank_lines() {
+    let root = make_temp_dir();
+    write(
+        &root.join("src/lib.rs"),
+        "// header\nfn f() {}\n\n/* a\n   /* b */\n*/\n",
+    );
+    write(&root.join("src/util/mod.rs"), "fn g() {}\nfn h() {}\n");
+    write(&root.join("tools/gen.py"), "\"\"\"Doc.\"\"\"\nprint('#')\n");
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust,python", "--lines"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("src/ (2, 3 code, 4 comment, 1 blank)"));
+    assert!(s.contains("util/ (1, 2 code, 0 comment, 0 blank)"));
+    assert!(s.contains("tools/ (1, 1 code, 1 comment, 0 blank)"));
+    assert!(s.contains("Total lines: 10 (4 code, 5 comment, 1 blank)"));
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust", "--lines", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("\"lines\":{\"code\":3,\"comment\":4,\"blank\":1},\"scan_time_secs\""));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn byte_totals_roll_up_and_can_drive_counts() {
+    let root = make_temp_dir();
+    write(&root.join("big/a.rs"), &"x".repeat(3 * 1024 * 1024));
+    write(&root.join("big/nested/b.rs"), &"x".repeat(512 * 1024));
+    write(&root.join("small/c.rs"), &"x".repeat(100));
+    write(&root.join("small/d.rs"), &"x".repeat(20));
+
+    let s = run(&root, &["--lang", "rust", "--count-by", "bytes"]);
+    assert!(s.contains("big/ (3.5 MiB)"));
+    assert!(s.contains("nested/ (512.0 KiB)"));
+    assert!(s.contains("small/ (120 B)"));
+    assert!(s.contains("Total size: 3.5 MiB (3670136 bytes)"));
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "rust",
+            "--count-by",
+            "bytes",
+            "--min-count",
+            "1024",
+        ],
+    );
+    assert!(s.contains("big/"));
+    assert!(!s.contains("small/"));
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "rust",
+            "--format",
+            "json",
+            "--count-mode",
+            "direct",
+        ],
+    );
+    assert!(s.contains("\"count_by\":\"files\""));
+    assert!(s.contains("\"total_bytes\":3670136"));
+    assert!(s.contains("\"name\":\"small\",\"path\":"));
+    assert!(s.contains("\"files\":2,\"bytes\":120,"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[cfg(unix)]
+#[test]
+fn csv_and_tsv_list_visible_directories() {
+    let root = make_temp_dir();
+    write(&root.join("src/main.rs"), "fn main() {}\n");
+    write(&root.join("src/util/mod.rs"), "\n");
+    write(&root.join("a,\"b\"/x.py"), "x = 1\n");
+    write(&root.join("empty/readme.txt"), "");
+
+    let s = run(&root, &["--lang", "rust,python", "--format", "csv"]);
+    let lines = s.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[0],
+        "path,depth,direct_files,tree_files,direct_bytes,tree_bytes,python,rust"
+    );
+    assert_eq!(lines[1], ".,0,0,3,0,20,1,2");
+    assert_eq!(lines[2], "\"a,\"\"b\"\"\",1,1,1,6,6,1,0");
+    assert_eq!(lines[3], "src,1,1,2,13,14,0,2");
+    assert!(lines[4].starts_with("src/util,2,1,1,"));
+    assert_eq!(lines.len(), 5);
+    assert!(!s.contains("empty"));
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "rust,python",
+            "--format",
+            "tsv",
+            "--max-depth",
+            "1",
+            "--count-mode",
+            "direct",
+        ],
+    );
+    let lines = s.lines().collect::<Vec<_>>();
+    assert_eq!(lines[1], ".\t0\t0\t3\t0\t20\t0\t0");
+    assert_eq!(lines[2], "a,\"b\"\t1\t1\t1\t6\t6\t1\t0");
+    assert_eq!(lines.len(), 4);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn markdown_report_has_tree_and_summary() {
+    let root = make_temp_dir();
+    write(&root.join("src/main.rs"), "fn main() {}\n");
+    write(&root.join("src/`tick`/lib.rs"), "\n");
+    write(&root.join("tools/run.py"), "print(1)\n");
+    write(&root.join("empty/readme.txt"), "");
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust,python", "--format", "markdown"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+    let root_name = root.file_name().unwrap().to_string_lossy();
+
+    assert!(s.starts_with("# python,rust file statistics\n\n"));
+    assert!(s.contains(&format!(
+        "- `{root_name}/` (3) — rust 2, python 1\n  - `src/` (2) — rust 2\n    - `` `tick`/ `` (1) — rust 1\n  - `tools/` (1) — python 1\n"
+    )));
+    assert!(!s.contains("empty"));
+    assert!(s.contains("| Metric | Value |\n| --- | --- |\n| Total matching files | 3 |\n"));
+    assert!(s.contains("| Languages | rust 2, python 1 |\n"));
+    assert!(s.contains("node\\_modules"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[cfg(unix)]
+#[test]
+fn html_report_is_self_contained() {
+    let root = make_temp_dir();
+    write(&root.join("src/main.rs"), "fn main() {}\n");
+    write(&root.join("src/<script>/lib.rs"), "\n");
+    write(&root.join("tools/run.py"), "print(1)\n");
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust,python", "--format", "html", "--lines"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let s = String::from_utf8_lossy(&out.stdout);
+
+    assert!(s.starts_with("<!DOCTYPE html>"));
+    assert!(!s.contains("http://") && !s.contains("https://"));
+    assert!(s.contains("<title>python,rust file statistics</title>"));
+    assert!(s.contains("<th data-key=\"code\" class=\"num\">Code</th>"));
+    assert!(s.contains("<tr><th>Total matching files</th><td>3</td></tr>"));
+    assert!(s.contains("<tr><th>Languages</th><td>rust 2, python 1</td></tr>"));
+    assert_eq!(s.matches("<script").count(), 2);
+
+    let start = "<script id=\"data\" type=\"application/json\">";
+    let data = &s[s.find(start).unwrap() + start.len()..];
+    let data = &data[..data.find("</script>").unwrap()];
+    let tree = serde_json::from_str::<Value>(data).expect("valid JSON");
+    assert_eq!(tree.get("path").and_then(|v| v.as_str()), Some("."));
+    assert_eq!(tree.get("files").and_then(|v| v.as_u64()), Some(3));
+    let src = &tree.get("children").and_then(|v| v.as_array()).unwrap()[0];
+    let nested = &src.get("children").and_then(|v| v.as_array()).unwrap()[0];
+    assert_eq!(
+        nested.get("name").and_then(|v| v.as_str()),
+        Some("<script>")
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn svg_treemap_nests_visible_directories() {
+    let root = make_temp_dir();
+    write(&root.join("big/a.rs"), "");
+    write(&root.join("big/b.rs"), "");
+    write(&root.join("big/deep/c.rs"), "");
+    write(&root.join("big/deep/d.rs"), "");
+    write(&root.join("small/e.rs"), "");
+    write(&root.join("a&b/f.rs"), "");
+    write(&root.join("a&b/g.rs"), "");
+
+    let s = run(&root, &["--lang", "rust", "--format", "svg-treemap"]);
+    assert!(s.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(s.ends_with("</svg>\n"));
+    assert!(s.contains("<title>rust treemap by files</title>"));
+    assert!(s.contains("<title>big (4 files)</title>"));
+    assert!(s.contains("<title>big/deep (2 files)</title>"));
+    assert!(s.contains(">a&amp;b/ (2 files)</text>"));
+    assert_eq!(s.matches("<rect ").count(), 5);
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "rust",
+            "--format",
+            "svg-treemap",
+            "--max-depth",
+            "1",
+        ],
+    );
+    assert!(s.contains("<title>big (4 files)</title>"));
+    assert!(!s.contains("deep"));
+    assert_eq!(s.matches("<rect ").count(), 4);
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "rust",
+            "--format",
+            "svg-treemap",
+            "--min-count",
+            "2",
+        ],
+    );
+    assert!(s.contains("<title>big/deep (2 files)</title>"));
+    assert!(!s.contains("small"));
+    assert_eq!(s.matches("<rect ").count(), 4);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[cfg(unix)]
+#[test]
+fn dot_graph_of_visible_directories() {
+    let root = make_temp_dir();
+    write(&root.join("app/main.rs"), "");
+    write(&root.join("app/say \"hi\"/lib.rs"), "");
+    write(&root.join("app/deep/er/x.rs"), "");
+    write(&root.join("docs/readme.txt"), "");
+
+    let s = run(&root, &["--lang", "rust", "--format", "dot"]);
+    let root_name = root.file_name().unwrap().to_string_lossy();
+    assert!(s.starts_with("digraph treestat {\n"));
+    assert!(s.ends_with("}\n"));
+    assert!(s.contains(&format!("  n0 [label=\"{root_name}/ (3)\", tooltip=")));
+    assert!(s.contains("fillcolor=\"#084594\", fontcolor=\"white\", fontsize=18.0];"));
+    assert!(s.contains("  n0 -> n1;\n  n1 [label=\"app/ (3)\""));
+    assert!(s.contains("[label=\"say \\\"hi\\\"/ (1)\""));
+    assert!(!s.contains("docs"));
+    assert_eq!(s.matches(" -> ").count(), 4);
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "rust",
+            "--format",
+            "dot",
+            "--max-depth",
+            "2",
+            "--show-empty",
+        ],
+    );
+    assert!(s.contains("[label=\"docs/ (0)\""));
+    assert!(s.contains("[label=\"deep/ (1)\""));
+    assert!(!s.contains("er/"));
+
+    let s = run(
+        &root,
+        &["--lang", "rust", "--format", "dot", "--min-count", "2"],
+    );
+    assert!(s.contains("app/ (3)"));
+    assert_eq!(s.matches(" -> ").count(), 1);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[cfg(unix)]
+#[test]
+fn flat_listing_is_sortable_and_null_separated() {
+    let root = make_temp_dir();
+    write(&root.join("a/one.rs"), "");
+    write(&root.join("b/one.rs"), "");
+    write(&root.join("b/two.rs"), "");
+    write(&root.join("b/c/three.rs"), "");
+    write(&root.join("line\nbreak/x.rs"), "");
+
+    assert_eq!(
+        run(&root, &["--lang", "rust", "--flat"]),
+        ".\t5\na\t1\nb\t3\nb/c\t1\nline\nbreak\t1\n"
+    );
+
+    assert_eq!(
+        run(
+            &root,
+            &[
+                "--lang",
+                "rust",
+                "--flat",
+                "--flat-sort",
+                "Count",
+                "--count-mode",
+                "direct"
+            ]
+        ),
+        "b\t2\na\t1\nb/c\t1\nline\nbreak\t1\n.\t0\n"
+    );
+    assert_eq!(
+        run(
+            &root,
+            &[
+                "--lang",
+                "rust",
+                "--format",
+                "flat",
+                "--max-depth",
+                "1",
+                "--null"
+            ]
+        ),
+        ".\t5\0a\t1\0b\t3\0line\nbreak\t1\0"
+    );
+
+    // Byte order of the whole path, whatever --sort says: `a/b` sorts after `a-c`.
+    write(&root.join("a-c/one.rs"), "");
+    write(&root.join("a/b/one.rs"), "");
+    let expected = ".\t7\na\t2\na-c\t1\na/b\t1\nb\t3\nb/c\t1\nline\nbreak\t1\n";
+    assert_eq!(run(&root, &["--lang", "rust", "--flat"]), expected);
+    assert_eq!(
+        run(&root, &["--lang", "rust", "--flat", "--sort", "count"]),
+        expected
+    );
+    assert_eq!(
+        run(&root, &["--lang", "rust", "--flat", "--reverse"]),
+        "line\nbreak\t1\nb/c\t1\nb\t3\na/b\t1\na-c\t1\na\t2\n.\t7\n"
+    );
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .arg("--null")
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--null requires --flat"));
+
+    // Argument order must not decide between `--flat` and another format.
+    for args in [["--flat", "--format", "csv"], ["--format", "csv", "--flat"]] {
+        let out = Command::new(bin_path())
+            .arg(&root)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("--flat cannot be combined"));
+    }
+    assert_eq!(
+        run(&root, &["--lang", "rust", "--flat", "--format", "flat"]),
+        expected
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn children_can_be_sorted_by_count_name_or_natural_order() {
+    let root = make_temp_dir();
+    write(&root.join("b/1.rs"), "");
+    write(&root.join("b/2.rs"), "");
+    write(&root.join("b/3.rs"), "");
+    write(&root.join("dir10/1.rs"), "");
+    write(&root.join("dir10/2.rs"), "");
+    write(&root.join("dir2/1.rs"), "");
+    write(&root.join("a/1.rs"), "");
+
+    let order = |extra: &[&str]| {
+        run(
+            &root,
+            &[&["--lang", "rust", "--format", "csv"], extra].concat(),
+        )
+        .lines()
+        .skip(2)
+        .map(|l| l.split(',').next().unwrap().to_string())
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(order(&[]), ["a", "b", "dir10", "dir2"]);
+    assert_eq!(order(&["--sort", "count"]), ["b", "dir10", "a", "dir2"]);
+    assert_eq!(order(&["--sort", "Natural"]), ["a", "b", "dir2", "dir10"]);
+    assert_eq!(
+        order(&["--sort", "natural", "--reverse"]),
+        ["dir10", "dir2", "b", "a"]
+    );
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust", "--sort", "count", "--format", "json"])
+        .output()
+        .unwrap();
+    let doc = serde_json::from_slice::<Value>(&out.stdout).unwrap();
+    let names = doc
+        .get("tree")
+        .and_then(|t| t.get("children"))
+        .and_then(|c| c.as_array())
+        .unwrap()
+        .iter()
+        .map(|c| c.get("name").and_then(|n| n.as_str()).unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["b", "dir10", "a", "dir2"]);
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--lang", "rust", "--sort", "count", "--reverse"])
+        .output()
+        .unwrap();
+    let s = String::from_utf8_lossy(&out.stdout);
+    assert!(s.contains("├── dir2/ (1)\n├── a/ (1)\n├── dir10/ (2)\n└── b/ (3)\n"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn collapse_joins_single_child_chains() {
+    let root = make_temp_dir();
+    write(&root.join("src/main/java/com/acme/api/A.java"), "");
+    write(&root.join("src/main/java/com/acme/impl/B.java"), "");
+    write(&root.join("src/main/java/com/acme/C.java"), "");
+    write(&root.join("lib/x/D.java"), "");
+
+    let s = run(&root, &["--lang", "java", "--collapse"]);
+    assert!(s.contains(
+        "├── lib/x/ (1)\n└── src/main/java/com/acme/ (3)\n    ├── api/ (1)\n    └── impl/ (1)\n"
+    ));
+
+    // Each joined directory still counts as a level for --max-depth.
+    let s = run(&root, &["--lang", "java", "--collapse", "--max-depth", "3"]);
+    assert!(s.contains("└── src/main/java/ (3)\n===="));
+
+    // Chains only join equal counts.
+    let s = run(
+        &root,
+        &["--lang", "java", "--collapse", "--count-mode", "direct"],
+    );
+    assert!(s.contains("└── src/main/java/com/ (0)\n    └── acme/ (1)\n"));
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "java",
+            "--collapse",
+            "--format",
+            "json",
+            "--max-depth",
+            "2",
+        ],
+    );
+    let doc = serde_json::from_str::<Value>(&s).unwrap();
+    let src = &doc
+        .get("tree")
+        .and_then(|t| t.get("children"))
+        .and_then(|c| c.as_array())
+        .unwrap()[1];
+    assert_eq!(src.get("name").and_then(|v| v.as_str()), Some("src/main"));
+    assert!(
+        src.get("path")
+            .and_then(|v| v.as_str())
+            .unwrap()
+            .ends_with("main")
+    );
+    assert_eq!(src.get("collapsed"), Some(&json!(["src", "main"])));
+    assert_eq!(
+        src.get("children").and_then(|c| c.as_array()),
+        Some(&vec![])
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn top_shows_largest_children_and_rolls_up_the_rest() {
+    let root = make_temp_dir();
+    for (dir, files) in [("a", 3), ("b", 1), ("c", 2), ("d", 1), ("e", 4)] {
+        for i in 0..files {
+            write(&root.join(format!("{dir}/{i}.rs")), "");
+        }
+    }
+    for dir in ["x", "y", "z"] {
+        write(&root.join(format!("e/{dir}/q.rs")), "");
+    }
+
+    let s = run(&root, &["--lang", "rust", "--top", "2"]);
+    assert!(s.contains(
+        "├── a/ (3)\n├── e/ (7)\n│   ├── x/ (1)\n│   ├── y/ (1)\n│   └── … (1 more dir, 1 file)\n└── … (3 more dirs, 4 files)\n"
+    ));
+
+    let s = run(
+        &root,
+        &[
+            "--lang",
+            "rust",
+            "--top",
+            "2",
+            "--format",
+            "json",
+            "--max-depth",
+            "1",
+        ],
+    );
+    let doc = serde_json::from_str::<Value>(&s).unwrap();
+    let children = doc
+        .get("tree")
+        .and_then(|t| t.get("children"))
+        .and_then(|c| c.as_array())
+        .unwrap();
+    assert_eq!(children.len(), 3);
+    let others = &children[2];
+    assert_eq!(
+        others.get("name").and_then(|v| v.as_str()),
+        Some("… (3 more dirs, 4 files)")
+    );
+    assert_eq!(others.get("synthetic"), Some(&Value::Bool(true)));
+    assert_eq!(others.get("dirs").and_then(|v| v.as_u64()), Some(3));
+    assert_eq!(others.get("files").and_then(|v| v.as_u64()), Some(4));
+    assert!(children[0].get("synthetic").is_none());
+    assert_eq!(others.get("path"), Some(&Value::Null));
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--top", "0"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+
+    let out = Command::new(bin_path())
+        .arg(&root)
+        .args(["--top", "2", "--format", "csv"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--top only applies to text and JSON"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[cfg(unix)]
+#[test]
+fn json_output_escapes_odd_names_and_parses_back() {
+    let root = make_temp_dir();
+    let odd = "tab\there \"quoted\" back\\slash\nnewline \u{1}";
+    write(&root.join(odd).join("a.rs"), "fn main() {}\n");
+
+    for pretty in [false, true] {
+        let mut cmd = Command::new(bin_path());
+        cmd.arg(&root).args(["--lang", "rust", "--format", "json"]);
+        if pretty {
+            cmd.arg("--json-pretty");
+        }
+        let out = cmd.output().unwrap();
+        assert!(out.status.success());
+        let s = String::from_utf8(out.stdout).unwrap();
+        assert_eq!(s.contains("\n  "), pretty);
+
+        let doc = serde_json::from_str::<Value>(&s).expect("valid JSON");
+        assert_eq!(doc.get("total_files").and_then(|v| v.as_u64()), Some(1));
+        let children = doc
+            .get("tree")
+            .and_then(|t| t.get("children"))
+            .and_then(|c| c.as_array())
+            .unwrap();
+        assert_eq!(children[0].get("name").and_then(|n| n.as_str()), Some(odd));
+    }
+
+    let _ = fs::remove_dir_all(root);
+}
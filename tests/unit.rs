@@ -1,10 +1,16 @@
 use std::collections::{BTreeSet, HashMap};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use treestat::cli::HeaderMode;
-use treestat::lang::{apply_header_mode, build_extensions, canonical_language_name};
-use treestat::model::DirData;
-use treestat::scanner::compute_tree_counts;
+use serde_json::{Value, json};
+use treestat::cli::{Cli, HeaderMode};
+use treestat::json;
+use treestat::lang::{
+    LanguageRegistry, apply_header_mode, interpreter_from_shebang, normalize_ext,
+};
+use treestat::lines::count_lines;
+use treestat::model::{DirData, LineCounts};
+use treestat::render::{Rect, natural_cmp, render_json, squarify};
+use treestat::scanner::{Gitignore, compute_tree_counts, is_ignored, scan_tree};
 
 #[test]
 fn header_modes_work() {
@@ -36,6 +42,8 @@ fn aggregate_counts() {
             name: "root".to_string(),
             children: [child.clone()].into_iter().collect(),
             direct_files: 1,
+            languages: [("python".to_string(), 1)].into_iter().collect(),
+            ..DirData::default()
         },
     );
     dirs.insert(
@@ -44,27 +52,602 @@ fn aggregate_counts() {
             name: "src".to_string(),
             children: BTreeSet::new(),
             direct_files: 2,
+            languages: [("rust".to_string(), 2)].into_iter().collect(),
+            ..DirData::default()
         },
     );
 
     let counts = compute_tree_counts(&root, &dirs);
-    assert_eq!(counts.get(&root), Some(&3));
-    assert_eq!(counts.get(&child), Some(&2));
+    assert_eq!(counts.get(&root).map(|c| c.files), Some(3));
+    assert_eq!(counts.get(&child).map(|c| c.files), Some(2));
+    assert_eq!(
+        counts[&root].languages,
+        [("python".to_string(), 1), ("rust".to_string(), 2)]
+            .into_iter()
+            .collect()
+    );
 }
 
 #[test]
 fn linguist_alias_and_multi_lang_extensions() {
-    assert_eq!(canonical_language_name("cpp").as_deref(), Some("c++"));
-
-    let exts = build_extensions(
-        &["c".to_string(), "cpp".to_string()],
-        &[],
-        HeaderMode::Include,
-    )
-    .expect("language aliases should resolve");
+    let registry = LanguageRegistry::default();
+    assert_eq!(
+        registry.canonical_language_name("cpp").as_deref(),
+        Some("c++")
+    );
+
+    let exts = registry
+        .build_extensions(
+            &["c".to_string(), "cpp".to_string()],
+            &[],
+            HeaderMode::Include,
+        )
+        .expect("language aliases should resolve");
     assert!(exts.contains("c"));
     assert!(exts.contains("cpp"));
     assert!(exts.contains("cc"));
     assert!(exts.contains("h"));
     assert!(exts.contains("hpp"));
 }
+
+#[test]
+fn gitignore_rules_follow_git_semantics() {
+    let root = Gitignore::parse(
+        Path::new(""),
+        "# comment\n*.log\n!keep.log\n/build\ndocs/\n**/gen/*.rs\nfoo/**/bar\ntmp[0-9]\n\\#hash\n",
+    );
+    let ignored = |p: &str, dir: bool| is_ignored(std::slice::from_ref(&root), Path::new(p), dir);
+
+    assert!(ignored("a/b/debug.log", false));
+    assert!(!ignored("a/keep.log", false));
+    assert!(ignored("build", true));
+    assert!(!ignored("src/build", true));
+    assert!(ignored("src/docs", true));
+    assert!(!ignored("src/docs", false));
+    assert!(ignored("gen/x.rs", false));
+    assert!(ignored("a/b/gen/x.rs", false));
+    assert!(!ignored("a/gen/sub/x.rs", false));
+    assert!(ignored("foo/bar", true));
+    assert!(ignored("foo/a/b/bar", true));
+    assert!(ignored("x/tmp7", false));
+    assert!(!ignored("x/tmpa", false));
+    assert!(ignored("#hash", false));
+}
+
+#[test]
+fn nested_gitignore_is_relative_to_its_directory() {
+    let stack = vec![
+        Gitignore::parse(Path::new(""), "*.gen\n"),
+        Gitignore::parse(Path::new("pkg"), "/local\n!keep.gen\n"),
+    ];
+
+    assert!(is_ignored(&stack, Path::new("pkg/local"), true));
+    assert!(!is_ignored(&stack, Path::new("pkg/sub/local"), true));
+    assert!(!is_ignored(&stack, Path::new("local"), true));
+    assert!(!is_ignored(&stack, Path::new("pkg/keep.gen"), false));
+    assert!(is_ignored(&stack, Path::new("other/keep.gen"), false));
+}
+
+#[test]
+fn shebang_interpreters_and_filenames() {
+    let registry = LanguageRegistry::default();
+    let cases = [
+        ("#!/usr/bin/env python3", Some("python3")),
+        ("#!/usr/bin/python3.12 -u", Some("python3")),
+        ("#!/usr/bin/env -S node --harmony", Some("node")),
+        ("#!/bin/sh", Some("sh")),
+        ("#! /usr/bin/env FOO=1 bash", Some("bash")),
+        ("# not a shebang", None),
+    ];
+    for (line, expected) in cases {
+        assert_eq!(
+            interpreter_from_shebang(line).as_deref(),
+            expected,
+            "{line}"
+        );
+    }
+
+    let selection = registry
+        .build_selection(&["python".to_string()], &[], &[], HeaderMode::Include)
+        .unwrap();
+    assert!(selection.interpreters.contains("python3"));
+    assert!(selection.filenames.contains("SConstruct"));
+    assert!(!selection.filenames.contains("Makefile"));
+}
+
+#[test]
+fn compound_extensions_match_longest_suffix() {
+    let registry = LanguageRegistry::default();
+    assert_eq!(normalize_ext(".D.TS").as_deref(), Some("d.ts"));
+    assert_eq!(normalize_ext("d..ts"), None);
+
+    let selection = registry
+        .build_selection(
+            &["typescript".to_string(), "go".to_string()],
+            &["d.ts".to_string()],
+            &["pb.go".to_string()],
+            HeaderMode::Include,
+        )
+        .unwrap();
+    let m = |name: &str| selection.match_extension(name);
+    assert_eq!(m("index.d.ts"), Some(("d.ts".to_string(), true)));
+    assert_eq!(m("index.ts"), Some(("ts".to_string(), true)));
+    assert_eq!(m("api.pb.go"), Some(("pb.go".to_string(), false)));
+    assert_eq!(m("main.go"), Some(("go".to_string(), true)));
+    assert_eq!(m(".eslintrc.ts"), Some(("ts".to_string(), true)));
+    assert_eq!(m("README"), None);
+}
+
+#[test]
+fn every_linguist_language_resolves() {
+    let registry = LanguageRegistry::default();
+    let names = registry.language_names();
+    assert!(names.len() > 200);
+    for name in &names {
+        assert_eq!(registry.canonical_language_name(name).as_ref(), Some(name));
+        let selection = registry
+            .build_selection(std::slice::from_ref(name), &[], &[], HeaderMode::Include)
+            .unwrap_or_else(|e| panic!("{name}: {e}"));
+        assert!(!selection.is_empty(), "{name} matches nothing");
+    }
+
+    assert_eq!(
+        registry.canonical_language_name("kotlin").as_deref(),
+        Some("kotlin")
+    );
+    assert_eq!(
+        registry.canonical_language_name("bash").as_deref(),
+        Some("shell")
+    );
+    assert_eq!(
+        registry.canonical_language_name("C#").as_deref(),
+        Some("c#")
+    );
+    assert_eq!(
+        registry.canonical_language_name("Cap'n Proto").as_deref(),
+        Some("cap'n proto")
+    );
+    assert_eq!(
+        registry.canonical_language_name("py").as_deref(),
+        Some("python")
+    );
+
+    // Grouped languages are counted with their parent, and `all` only means programming.
+    let ts = registry
+        .build_extensions(&["ts".to_string()], &[], HeaderMode::Include)
+        .unwrap();
+    assert!(ts.contains("tsx"));
+    let all = registry
+        .build_extensions(&[], &[], HeaderMode::Include)
+        .unwrap();
+    assert!(all.contains("kt"));
+    assert!(!all.contains("md"));
+    assert!(!all.contains("json"));
+}
+
+#[test]
+fn embedded_linguist_data_has_known_entries() {
+    let registry = LanguageRegistry::default();
+    let languages = registry.languages();
+    let find = |name: &str| {
+        languages
+            .iter()
+            .find(|l| l.name == name)
+            .unwrap_or_else(|| panic!("{name} is missing"))
+    };
+
+    let kotlin = find("kotlin");
+    assert_eq!(kotlin.display_name, "Kotlin");
+    assert_eq!(kotlin.kind.as_deref(), Some("programming"));
+    assert_eq!(kotlin.extensions, ["kt", "ktm", "kts"]);
+
+    let shell = find("shell");
+    assert!(shell.aliases.contains(&"bash".to_string()));
+    assert!(shell.extensions.contains(&"sh".to_string()));
+    assert!(shell.interpreters.contains(&"bash".to_string()));
+
+    let dockerfile = find("dockerfile");
+    assert!(dockerfile.filenames.contains(&"Dockerfile".to_string()));
+    let selection = registry
+        .build_selection(&["dockerfile".to_string()], &[], &[], HeaderMode::Include)
+        .unwrap();
+    assert_eq!(
+        selection
+            .filename_languages
+            .get("Dockerfile")
+            .map(String::as_str),
+        Some("dockerfile")
+    );
+}
+
+#[test]
+fn linguist_fields_treestat_does_not_use_are_skipped() {
+    // Shaped like an entry of the upstream `languages.yml`, with every field it carries.
+    let entry = r##"---
+"Fixture Lang":
+  type: programming
+  color: "#dea584"
+  aliases:
+  - fixture
+  extensions:
+  - ".fxl"
+  - ".fxl.in"
+  interpreters:
+  - fixture-script
+  tm_scope: source.fixture
+  ace_mode: text
+  codemirror_mode: rust
+  codemirror_mime_type: text/x-rustsrc
+  wrap: false
+  searchable: true
+  fs_name: Fixture
+  language_id: 999999
+"##;
+    let registry =
+        LanguageRegistry::new(&[("languages.yml".to_string(), entry.to_string())]).unwrap();
+    assert_eq!(
+        registry.canonical_language_name("fixture").as_deref(),
+        Some("fixture lang")
+    );
+    let selection = registry
+        .build_selection(&["fixture".to_string()], &[], &[], HeaderMode::Include)
+        .unwrap();
+    assert_eq!(
+        selection.extensions,
+        ["fxl", "fxl.in"].map(str::to_string).into()
+    );
+    assert!(selection.interpreters.contains("fixture-script"));
+}
+
+#[test]
+fn languages_files_apply_to_their_own_run() {
+    let root = std::env::temp_dir().join(format!("treestat-languages-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("schema")).unwrap();
+    std::fs::write(root.join("schema/a.xdl"), "record A {}\n").unwrap();
+    let file = root.join("languages.yml");
+    std::fs::write(&file, "XDL:\n  aliases: [xdsl]\n  extensions: [\".xdl\"]\n").unwrap();
+
+    let run = |extra: &[&str]| {
+        let mut argv = vec![root.to_string_lossy().into_owned()];
+        argv.extend(extra.iter().map(|a| a.to_string()));
+        treestat::run(Cli::parse(argv).unwrap())
+    };
+    // The built-in data, another run's file, or a second run with the same file must not
+    // get in the way.
+    assert_eq!(
+        LanguageRegistry::default().canonical_language_name("xdsl"),
+        None
+    );
+    let file = file.to_string_lossy().into_owned();
+    for _ in 0..2 {
+        let out = run(&["--languages-file", &file, "--lang", "xdsl"]).unwrap();
+        assert!(out.contains("Total matching files: 1"));
+    }
+    assert!(run(&["--lang", "xdsl"]).is_err());
+    let _ = std::fs::remove_dir_all(root);
+}
+
+#[test]
+fn line_counts_follow_comment_syntax() {
+    let registry = LanguageRegistry::default();
+    let rust = registry.comment_syntax("rust").unwrap();
+    let src = "\
+/* outer
+   /* nested */
+   still comment */
+fn main() { // trailing
+    let s = \"// not a comment\";
+
+    let t = \"/* not
+a comment */\"; /* real */
+}
+";
+    assert_eq!(
+        count_lines(src, rust),
+        LineCounts {
+            code: 5,
+            comment: 3,
+            blank: 1
+        }
+    );
+
+    let python = registry.comment_syntax("python").unwrap();
+    let src = "\
+def f():
+    \"\"\"Docstring.
+
+    # still docstring
+    \"\"\"
+    x = \"\"\"not a
+    docstring\"\"\"
+    y = '#' # comment after code
+    # comment
+";
+    assert_eq!(
+        count_lines(src, python),
+        LineCounts {
+            code: 4,
+            comment: 4,
+            blank: 1
+        }
+    );
+
+    let none = registry
+        .comment_syntax("other")
+        .cloned()
+        .unwrap_or_default();
+    assert_eq!(count_lines("a\n\n// b\n", &none).code, 2);
+}
+
+#[test]
+fn line_counts_skip_rust_char_literals_and_raw_strings() {
+    let registry = LanguageRegistry::default();
+    let rust = registry.comment_syntax("rust").unwrap();
+    let src = r###"fn f<'a>(s: &'a str) -> char {
+    let q = '"';
+    // one
+    let e = '\'';
+    // two
+    let b = b'\\';
+    let r = r"// not a comment";
+    let h = r#"quote " inside
+// still the string
+"#;
+    // three
+    'x'
+}
+"###;
+    assert_eq!(
+        count_lines(src, rust),
+        LineCounts {
+            code: 10,
+            comment: 3,
+            blank: 0
+        }
+    );
+}
+
+#[test]
+fn line_counts_close_lua_long_strings() {
+    let registry = LanguageRegistry::default();
+    let lua = registry.comment_syntax("lua").unwrap();
+    let src = r#"local s = [[
+-- inside the string
+]]
+-- a comment
+local t = [=[ ]] ]=] -- trailing
+--[[ block
+]]
+"#;
+    assert_eq!(
+        count_lines(src, lua),
+        LineCounts {
+            code: 4,
+            comment: 3,
+            blank: 0
+        }
+    );
+}
+
+#[test]
+fn json_writer_escapes_and_round_trips() {
+    let value = json::Value::object([
+        ("plain", json::Value::from("a/b")),
+        (
+            "escapes",
+            "quote\" back\\ nl\n cr\r tab\t bs\u{8} ff\u{c}".into(),
+        ),
+        ("control", "\u{1}\u{1f}\u{7f}".into()),
+        ("unicode", "ü 日本 🦀".into()),
+        (
+            "numbers",
+            vec![json::Value::UInt(u64::MAX), json::Value::Float(0.5)].into(),
+        ),
+        (
+            "empty",
+            json::Value::object(Vec::<(String, json::Value)>::new()),
+        ),
+        (
+            "nested",
+            vec![json::Value::Null, true.into(), json::Value::Array(vec![])].into(),
+        ),
+    ]);
+    let expected = json!({
+        "plain": "a/b",
+        "escapes": "quote\" back\\ nl\n cr\r tab\t bs\u{8} ff\u{c}",
+        "control": "\u{1}\u{1f}\u{7f}",
+        "unicode": "ü 日本 🦀",
+        "numbers": [u64::MAX, 0.5],
+        "empty": {},
+        "nested": [null, true, []],
+    });
+
+    let compact = value.to_json(false);
+    assert!(!compact.contains('\n'));
+    assert!(compact.contains(r#""escapes":"quote\" back\\ nl\n cr\r tab\t bs\b ff\f""#));
+    assert!(compact.contains("\"control\":\"\\u0001\\u001f\u{7f}\""));
+    assert_eq!(serde_json::from_str::<Value>(&compact).unwrap(), expected);
+
+    let pretty = value.to_json(true);
+    assert!(pretty.contains("\n  \"plain\": \"a/b\",\n"));
+    assert_eq!(serde_json::from_str::<Value>(&pretty).unwrap(), expected);
+}
+
+/// Checks `value` against the subset of JSON Schema used by `schema/treestat.schema.json`.
+fn validate(value: &Value, schema: &Value, root: &Value, at: &str) -> Result<(), String> {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.strip_prefix("#/$defs/").expect("local $ref");
+        let target = root
+            .get("$defs")
+            .and_then(|d| d.get(name))
+            .expect("$ref target");
+        return validate(value, target, root, at);
+    }
+    if let Some(expected) = schema.get("const")
+        && value != expected
+    {
+        return Err(format!("{at}: expected {expected:?}"));
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array)
+        && !options.contains(value)
+    {
+        return Err(format!("{at}: {value:?} is not one of {options:?}"));
+    }
+    if let Some(types) = schema.get("type") {
+        let types = match types {
+            Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+            other => vec![other.as_str().unwrap()],
+        };
+        let matches = |t: &str| {
+            t == "integer" && (value.is_u64() || value.is_i64())
+                || matches!(
+                    (t, value),
+                    ("null", Value::Null)
+                        | ("boolean", Value::Bool(_))
+                        | ("number", Value::Number(_))
+                        | ("string", Value::String(_))
+                        | ("array", Value::Array(_))
+                        | ("object", Value::Object(_))
+                )
+        };
+        if !types.iter().any(|t| matches(t)) {
+            return Err(format!("{at}: {value:?} is not of type {types:?}"));
+        }
+    }
+    if let Some(min) = schema.get("minimum").and_then(Value::as_u64)
+        && value.as_f64().is_some_and(|n| n < min as f64)
+    {
+        return Err(format!("{at}: below minimum {min}"));
+    }
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (idx, item) in items.iter().enumerate() {
+            validate(item, item_schema, root, &format!("{at}[{idx}]"))?;
+        }
+    }
+    if let Value::Object(fields) = value {
+        for key in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let key = key.as_str().unwrap();
+            if !fields.contains_key(key) {
+                return Err(format!("{at}: missing {key}"));
+            }
+        }
+        for (key, field) in fields {
+            let path = format!("{at}.{key}");
+            match schema.get("properties").and_then(|p| p.get(key)) {
+                Some(property) => validate(field, property, root, &path)?,
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => return Err(format!("{path}: not in the schema")),
+                    Some(extra @ Value::Object(_)) => validate(field, extra, root, &path)?,
+                    _ => {}
+                },
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn json_output_matches_published_schema() {
+    let registry = LanguageRegistry::default();
+    let schema: Value =
+        serde_json::from_str(include_str!("../schema/treestat.schema.json")).unwrap();
+    let root = std::env::temp_dir().join(format!("treestat-schema-{}", std::process::id()));
+    for (path, content) in [
+        ("src/lib.rs", "// lib\nfn f() {}\n"),
+        ("src/deep/er/x.rs", "fn g() {}\n"),
+        ("tools/gen.py", "print(1)\n"),
+    ] {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    let root = root.canonicalize().unwrap();
+
+    for args in [
+        vec!["--lang", "rust,python", "--collapse"],
+        vec![
+            "--lines",
+            "--max-depth",
+            "1",
+            "--prune",
+            "--exclude",
+            "*.tmp",
+        ],
+        vec![
+            "--count-mode",
+            "direct",
+            "--count-by",
+            "bytes",
+            "--threads",
+            "2",
+            "--top",
+            "1",
+        ],
+    ] {
+        let mut argv = vec![root.to_string_lossy().into_owned()];
+        argv.extend(args.iter().map(|a| a.to_string()));
+        let cli = Cli::parse(argv).unwrap();
+        let selection = registry
+            .build_selection(&cli.langs, &cli.ext, &cli.exclude_ext, cli.headers)
+            .unwrap();
+        let scan = scan_tree(&root, &registry, &selection, &cli, &[]).unwrap();
+        let counts = compute_tree_counts(&root, &scan.dirs);
+
+        for pretty in [false, true] {
+            let out = render_json(&scan, &counts, &selection, &cli.langs, &cli, 0.25, pretty);
+            let doc: Value = serde_json::from_str(&out).unwrap();
+            if let Err(e) = validate(&doc, &schema, &schema, "$") {
+                panic!("{args:?}: {e}");
+            }
+        }
+    }
+
+    let bad = json!({"schema_version": 1});
+    assert!(validate(&bad, &schema, &schema, "$").is_err());
+    let _ = std::fs::remove_dir_all(root);
+}
+
+#[test]
+fn squarify_fills_the_rectangle_without_overlap() {
+    // The example from the squarified treemap paper.
+    let weights = [6.0, 6.0, 4.0, 3.0, 2.0, 2.0, 1.0];
+    let rect = Rect {
+        x: 0.0,
+        y: 0.0,
+        w: 6.0,
+        h: 4.0,
+    };
+    let rects = squarify(&weights, rect);
+    assert_eq!(rects.len(), weights.len());
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+    assert!(close(rects[0].w, 3.0) && close(rects[0].h, 2.0));
+    assert!(close(rects[1].y, 2.0) && close(rects[1].h, 2.0));
+
+    for (r, w) in rects.iter().zip(weights) {
+        assert!(close(r.w * r.h, w));
+        assert!(r.x >= -1e-9 && r.y >= -1e-9);
+        assert!(r.x + r.w <= 6.0 + 1e-9 && r.y + r.h <= 4.0 + 1e-9);
+    }
+    for (i, a) in rects.iter().enumerate() {
+        for b in &rects[i + 1..] {
+            let overlap_w = (a.x + a.w).min(b.x + b.w) - a.x.max(b.x);
+            let overlap_h = (a.y + a.h).min(b.y + b.h) - a.y.max(b.y);
+            assert!(overlap_w <= 1e-9 || overlap_h <= 1e-9);
+        }
+    }
+}
+
+#[test]
+fn natural_order_compares_numbers_by_value() {
+    let mut names = vec!["dir10", "Dir1", "dir2", "b", "dir02", "v1.10", "v1.9"];
+    names.sort_by(|a, b| natural_cmp(a, b).then_with(|| a.cmp(b)));
+    assert_eq!(
+        names,
+        ["b", "Dir1", "dir02", "dir2", "dir10", "v1.9", "v1.10"]
+    );
+}